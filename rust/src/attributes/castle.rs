@@ -2,13 +2,15 @@ use bit_vec::BitVec;
 use serde::{Deserialize, Serialize};
 
 use crate::attributes::main::{
-    InfoOption, MoveData, OptionValue, PieceAttributeTrait, PieceTraitInfo,
+    bw, InfoOption, MoveData, OptionType, OptionValue, PieceAttributeTrait, PieceTraitInfo,
 };
 use crate::board::Board;
-use crate::piece::Piece;
-use crate::util::ILoc;
+use crate::error::LinnyError;
+use crate::piece::{ColorTrait, DefaultPiece, Piece, PieceType};
+use crate::util::{ILoc, Loc};
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "bare"), derive(schemars::JsonSchema))]
 pub(crate) struct Castle {
     pub(crate) destinations: Vec<ILoc>,
     pub(crate) black_destinations: Option<Vec<ILoc>>,
@@ -16,32 +18,214 @@ pub(crate) struct Castle {
     pub(crate) black_rook: Option<Vec<ILoc>>,
     pub(crate) rook_destination: Vec<ILoc>,
     pub(crate) black_rook_destination: Option<Vec<ILoc>>,
+    /// The piece type the piece castles with, a rook unless given
+    #[serde(default = "default_rook_piece")]
+    pub(crate) rook_piece: PieceType,
 }
+impl Default for Castle {
+    fn default() -> Self {
+        Self {
+            destinations: vec![],
+            black_destinations: None,
+            rook: vec![],
+            black_rook: None,
+            rook_destination: vec![],
+            black_rook_destination: None,
+            rook_piece: default_rook_piece(),
+        }
+    }
+}
+
+fn default_rook_piece() -> PieceType {
+    DefaultPiece::ROOK
+}
+
+/// Every square on the straight line from `from` to `to`, excluding `from`
+fn line(from: Loc, to: Loc) -> Vec<Loc> {
+    let step = ILoc(
+        (to.0 as i16 - from.0 as i16).signum(),
+        (to.1 as i16 - from.1 as i16).signum(),
+    );
+    let mut squares = vec![];
+    let mut current = from.as_iLoc();
+    while current != to.as_iLoc() {
+        current += step;
+        squares.push(Loc::from(current));
+    }
+    squares
+}
+
 impl PieceAttributeTrait for Castle {
     fn moves(&self, board: &Board, piece: &Piece, moves: &mut Vec<MoveData>) {
-        todo!()
-    }
+        if !board.is_first_move(piece) {
+            return;
+        }
+
+        let destinations = bw(&self.destinations, &self.black_destinations, piece.color);
+        let rooks = bw(&self.rook, &self.black_rook, piece.color);
+        let rook_destinations = bw(
+            &self.rook_destination,
+            &self.black_rook_destination,
+            piece.color,
+        );
 
-    fn attacks(&self, board: &Board, piece: &Piece, attacks: &mut BitVec) {
-        todo!()
+        for ((destination, rook), rook_destination) in
+            destinations.iter().zip(rooks).zip(rook_destinations)
+        {
+            let to = (piece.loc.as_iLoc() + *destination).try_as_loc();
+            let rook_loc = (piece.loc.as_iLoc() + *rook).try_as_loc();
+            let rook_to = (piece.loc.as_iLoc() + *rook_destination).try_as_loc();
+
+            if let Some(to) = to
+                && let Some(rook_loc) = rook_loc
+                && let Some(rook_to) = rook_to
+                && board.valid_loc(&to)
+                && board.valid_loc(&rook_to)
+                && let Some(rook_piece) = board.get(&rook_loc)
+                && rook_piece.color == piece.color
+                && rook_piece.info_index == self.rook_piece
+                && board.is_first_move(&rook_piece)
+            {
+                // Everything between the piece and its rook, and both destinations, must be empty
+                let mut path = line(piece.loc, rook_loc);
+                path.extend(line(piece.loc, to));
+                path.extend(line(rook_loc, rook_to));
+                let blocked = path.iter().any(|loc| {
+                    *loc != piece.loc && *loc != rook_loc && board.check_loc(loc).is_some()
+                });
+                if blocked {
+                    continue;
+                }
+
+                // The piece can't castle out of, through, or into an attacked square
+                let mut king_path = line(piece.loc, to);
+                king_path.push(piece.loc);
+                let attacked = king_path.iter().any(|loc| {
+                    board.attacks[piece.color.other()]
+                        .get(board.loc_as_bit(loc))
+                        .contains(&true)
+                });
+                if attacked {
+                    continue;
+                }
+
+                moves.push(MoveData {
+                    castle: Some((rook_loc, rook_to)),
+                    promotion: None,
+                    piece: *piece,
+                    to,
+                    capture: None,
+                });
+            }
+        }
     }
 
+    /// Castling never captures, so it does not attack anything
+    fn attacks(&self, _: &Board, _: &Piece, _: &mut BitVec) {}
+
     fn info(&self) -> PieceTraitInfo {
         PieceTraitInfo {
             name: "Castling",
             description: "Castling moves the piece to a given destination and moves a the given rook to its destination, only if the way is clear.",
             example: Some("King castling"),
-            options: vec![InfoOption {
-                name: "destinations",
-                description: todo!(),
-                optional: todo!(),
-                options: todo!(),
-                example: todo!()
-            }]
+            options: vec![
+                InfoOption {
+                    optional: false,
+                    name: "destinations",
+                    description: "Where the piece lands for each castle, relative to the piece.",
+                    options: OptionType::ILocVec,
+                    example: Some("The king moves two squares towards the rook."),
+                },
+                InfoOption {
+                    optional: true,
+                    name: "black_destinations",
+                    description: "Where the piece lands for each castle if the piece is black. If not provided, the white destinations will be used.",
+                    options: OptionType::ILocVec,
+                    example: Some("The king moves two squares towards the rook."),
+                },
+                InfoOption {
+                    optional: false,
+                    name: "rook",
+                    description: "Where the rook of each castle is, relative to the piece. The rook must not have moved.",
+                    options: OptionType::ILocVec,
+                    example: Some("The rooks start in the corners."),
+                },
+                InfoOption {
+                    optional: true,
+                    name: "black_rook",
+                    description: "Where the rook of each castle is if the piece is black. If not provided, the white rooks will be used.",
+                    options: OptionType::ILocVec,
+                    example: Some("The rooks start in the corners."),
+                },
+                InfoOption {
+                    optional: false,
+                    name: "rook_destination",
+                    description: "Where the rook of each castle lands, relative to the piece.",
+                    options: OptionType::ILocVec,
+                    example: Some("The rook jumps to the other side of the king."),
+                },
+                InfoOption {
+                    optional: true,
+                    name: "black_rook_destination",
+                    description: "Where the rook of each castle lands if the piece is black. If not provided, the white rook destinations will be used.",
+                    options: OptionType::ILocVec,
+                    example: Some("The rook jumps to the other side of the king."),
+                },
+                InfoOption {
+                    optional: true,
+                    name: "rook_piece",
+                    description: "The piece the piece castles with. If not provided, it castles with rooks.",
+                    options: OptionType::DefaultPiece,
+                    example: Some("The king castles with rooks."),
+                },
+            ],
+        }
+    }
+
+    fn set_option(&mut self, name: &str, value: &Option<OptionValue>) -> Result<(), LinnyError> {
+        if let Some(value) = value {
+            match name {
+                "destinations" => self.destinations = value.as_iloc_vec(name)?,
+                "black_destinations" => self.black_destinations = Some(value.as_iloc_vec(name)?),
+                "rook" => self.rook = value.as_iloc_vec(name)?,
+                "black_rook" => self.black_rook = Some(value.as_iloc_vec(name)?),
+                "rook_destination" => self.rook_destination = value.as_iloc_vec(name)?,
+                "black_rook_destination" => {
+                    self.black_rook_destination = Some(value.as_iloc_vec(name)?)
+                }
+                "rook_piece" => self.rook_piece = value.as_default_piece(name)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn get_option(&self, name: &str) -> Option<OptionValue> {
+        match name {
+            "destinations" => Some(OptionValue::ILocVec(self.destinations.clone())),
+            "black_destinations" => self.black_destinations.clone().map(OptionValue::ILocVec),
+            "rook" => Some(OptionValue::ILocVec(self.rook.clone())),
+            "black_rook" => self.black_rook.clone().map(OptionValue::ILocVec),
+            "rook_destination" => Some(OptionValue::ILocVec(self.rook_destination.clone())),
+            "black_rook_destination" => self
+                .black_rook_destination
+                .clone()
+                .map(OptionValue::ILocVec),
+            "rook_piece" => Some(OptionValue::DefaultPiece(self.rook_piece)),
+            _ => None,
         }
     }
 
-    fn set_option(&mut self, name: &str, value: &Option<OptionValue>) {
-        todo!()
+    fn problems(&self) -> Vec<String> {
+        let lengths = [
+            self.destinations.len(),
+            self.rook.len(),
+            self.rook_destination.len(),
+        ];
+        if lengths.iter().any(|length| *length != lengths[0]) {
+            vec!["'destinations', 'rook' and 'rook_destination' should have one entry for each castle.".to_string()]
+        } else {
+            vec![]
+        }
     }
 }
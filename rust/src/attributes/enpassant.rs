@@ -6,6 +6,7 @@ use crate::attributes::main::{
     bw, InfoOption, MoveData, OptionType, OptionValue, PieceAttributeTrait, PieceTraitInfo,
 };
 use crate::board::Board;
+use crate::error::LinnyError;
 use crate::piece::{Piece, PieceType};
 use crate::util::ILoc;
 
@@ -19,32 +20,31 @@ pub(crate) struct EnPassant {
 }
 impl PieceAttributeTrait for EnPassant {
     fn moves(&self, board: &Board, piece: &Piece, moves: &mut Vec<MoveData>) {
+        let target = match board.en_passant_target() {
+            Some(target) if target.info_index == self.piece && target.color != piece.color => {
+                target
+            }
+            _ => return,
+        };
+
         let offsets = bw(&self.offsets, &self.black_offsets, piece.color);
         for offset in offsets.iter() {
             let loc = (piece.loc.as_iLoc() + *offset).try_as_loc();
-            if let Some(loc) = loc {
-                if !board.valid_loc(&loc) {
-                    return;
-                }
-
-                let occupied = board.check_loc(&loc);
-                if let Some(color) = occupied {
-                    if color != piece.color {
-                        let capture_offset = bw(
-                            &self.capture_offset,
-                            &self.black_capture_offset,
-                            piece.color,
-                        );
-                        let to = (loc.as_iLoc() + *capture_offset).try_as_loc();
-                        if let Some(to) = to {
-                            moves.push(MoveData {
-                                castle: None,
-                                piece: *piece,
-                                to,
-                                capture: Some(loc),
-                            });
-                        }
-                    }
+            if let Some(loc) = loc && loc == target.loc {
+                let capture_offset = bw(
+                    &self.capture_offset,
+                    &self.black_capture_offset,
+                    piece.color,
+                );
+                let to = (loc.as_iLoc() + *capture_offset).try_as_loc();
+                if let Some(to) = to && board.valid_loc(&to) && board.check_loc(&to).is_none() {
+                    moves.push(MoveData {
+                        castle: None,
+                        promotion: None,
+                        piece: *piece,
+                        to,
+                        capture: Some(loc),
+                    });
                 }
             }
         }
@@ -56,7 +56,7 @@ impl PieceAttributeTrait for EnPassant {
     fn info(&self) -> PieceTraitInfo {
         PieceTraitInfo {
             name: "En passant",
-            description: "Allows the piece to capture a given piece that has just moved and is offset by a given amount.",
+            description: "Allows the piece to capture a given piece that has just made a first move of more than one square and is offset by a given amount.",
             example: Some("Pawn en passant"),
             options: vec![
                 InfoOption {
@@ -98,18 +98,28 @@ impl PieceAttributeTrait for EnPassant {
         }
     }
 
-    fn set_option(&mut self, name: &str, value: &Option<OptionValue>) {
+    fn set_option(&mut self, name: &str, value: &Option<OptionValue>) -> Result<(), LinnyError> {
         if let Some(value) = value {
             match name {
-                "offsets" => self.offsets = value.as_iloc_vec().unwrap(),
-                "black_offsets" => self.black_offsets = Some(value.as_iloc_vec().unwrap()),
-                "capture_offset" => self.capture_offset = value.as_iloc().unwrap(),
-                "black_capture_offset" => {
-                    self.black_capture_offset = Some(value.as_iloc().unwrap())
-                }
-                "piece" => self.piece = value.as_default_piece().unwrap(),
+                "offsets" => self.offsets = value.as_iloc_vec(name)?,
+                "black_offsets" => self.black_offsets = Some(value.as_iloc_vec(name)?),
+                "capture_offset" => self.capture_offset = value.as_iloc(name)?,
+                "black_capture_offset" => self.black_capture_offset = Some(value.as_iloc(name)?),
+                "piece" => self.piece = value.as_default_piece(name)?,
                 _ => {}
             }
         }
+        Ok(())
+    }
+
+    fn get_option(&self, name: &str) -> Option<OptionValue> {
+        match name {
+            "offsets" => Some(OptionValue::ILocVec(self.offsets.clone())),
+            "black_offsets" => self.black_offsets.clone().map(OptionValue::ILocVec),
+            "capture_offset" => Some(OptionValue::ILoc(self.capture_offset)),
+            "black_capture_offset" => self.black_capture_offset.map(OptionValue::ILoc),
+            "piece" => Some(OptionValue::DefaultPiece(self.piece)),
+            _ => None,
+        }
     }
 }
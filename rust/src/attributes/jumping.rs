@@ -6,8 +6,9 @@ use crate::attributes::main::{
     PieceTraitInfo,
 };
 use crate::board::Board;
+use crate::error::LinnyError;
 use crate::piece::Piece;
-use crate::util::ILoc;
+use crate::util::{ILoc, Loc};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[cfg_attr(not(feature = "bare"), derive(schemars::JsonSchema))]
@@ -17,14 +18,32 @@ pub(crate) struct Jumping {
     pub(crate) capture: bool,
     pub(crate) capture_only: bool,
     pub(crate) first_move_only: bool,
+    /// Pieces on the squares a straight or diagonal jump passes over stop it
+    #[serde(default)]
+    pub(crate) blockable: bool,
+}
+impl Jumping {
+    /// Checks if a jump is stopped by a piece it passes over, jumps that aren't straight or diagonal never are
+    fn blocked(&self, board: &Board, from: Loc, dir: ILoc) -> bool {
+        let (x, y) = (dir.0.abs(), dir.1.abs());
+        if !self.blockable || (x != 0 && y != 0 && x != y) {
+            return false;
+        }
+
+        let distance = x.max(y);
+        let step = ILoc(dir.0.signum(), dir.1.signum());
+        let mut current = from.as_iLoc();
+        (1..distance).any(|_| {
+            current += step;
+            current
+                .try_as_loc()
+                .map_or(false, |loc| board.check_loc(&loc).is_some())
+        })
+    }
 }
 impl PieceAttributeTrait for Jumping {
     fn moves(&self, board: &Board, piece: &Piece, moves: &mut Vec<MoveData>) {
-        if self.first_move_only
-            && board.first_moves[piece.color]
-                .get(board.loc_as_bit(&piece.loc))
-                .contains(&true)
-        {
+        if self.first_move_only && !board.is_first_move(piece) {
             return;
         }
 
@@ -32,7 +51,7 @@ impl PieceAttributeTrait for Jumping {
         for dir in directions {
             let loc = &(piece.loc.as_iLoc() + *dir).try_as_loc();
             if let Some(loc) = loc {
-                if !board.valid_loc(loc) {
+                if !board.valid_loc(loc) || self.blocked(board, piece.loc, *dir) {
                     continue;
                 }
 
@@ -44,6 +63,7 @@ impl PieceAttributeTrait for Jumping {
                         if color != piece.color {
                             moves.push(MoveData {
                                 castle: None,
+                                promotion: None,
                                 piece: *piece,
                                 to: *loc,
                                 capture: Some(*loc),
@@ -56,6 +76,7 @@ impl PieceAttributeTrait for Jumping {
                 if !self.capture_only {
                     moves.push(MoveData {
                         castle: None,
+                        promotion: None,
                         piece: *piece,
                         to: *loc,
                         capture: None,
@@ -66,12 +87,7 @@ impl PieceAttributeTrait for Jumping {
     }
 
     fn attacks(&self, board: &Board, piece: &Piece, attacks: &mut BitVec) {
-        if !self.capture
-            || (self.first_move_only
-                && board.first_moves[piece.color]
-                    .get(board.loc_as_bit(&piece.loc))
-                    .contains(&true))
-        {
+        if !self.capture || (self.first_move_only && !board.is_first_move(piece)) {
             return;
         }
 
@@ -79,7 +95,7 @@ impl PieceAttributeTrait for Jumping {
         for dir in directions {
             let loc = &(piece.loc.as_iLoc() + *dir).try_as_loc();
             if let Some(loc) = loc {
-                if !board.valid_loc(loc) {
+                if !board.valid_loc(loc) || self.blocked(board, piece.loc, *dir) {
                     continue;
                 }
                 attacks.set(board.loc_as_bit(loc), true);
@@ -123,20 +139,54 @@ impl PieceAttributeTrait for Jumping {
                     example: Some("Pawn"),
                 },
                 first_move_option(),
+                InfoOption {
+                    optional: true,
+                    name: "blockable",
+                    description: "The jump is stopped by pieces on the squares it passes over. Only straight and diagonal jumps pass over squares.",
+                    options: OptionType::Bool,
+                    example: Some("Pawn (double move)"),
+                },
             ],
         }
     }
 
-    fn set_option(&mut self, name: &str, value: &Option<OptionValue>) {
+    fn set_option(&mut self, name: &str, value: &Option<OptionValue>) -> Result<(), LinnyError> {
         if let Some(value) = value {
             match name {
-                "directions" => self.directions = value.as_iloc_vec().unwrap(),
-                "black_directions" => self.black_directions = Some(value.as_iloc_vec().unwrap()),
-                "capture" => self.capture = value.as_bool().unwrap(),
-                "capture_only" => self.capture_only = value.as_bool().unwrap(),
-                "first_move_only" => self.first_move_only = value.as_bool().unwrap(),
+                "directions" => self.directions = value.as_iloc_vec(name)?,
+                "black_directions" => self.black_directions = Some(value.as_iloc_vec(name)?),
+                "capture" => self.capture = value.as_bool(name)?,
+                "capture_only" => self.capture_only = value.as_bool(name)?,
+                "first_move_only" => self.first_move_only = value.as_bool(name)?,
+                "blockable" => self.blockable = value.as_bool(name)?,
                 _ => {}
             }
         }
+        Ok(())
+    }
+
+    fn get_option(&self, name: &str) -> Option<OptionValue> {
+        match name {
+            "directions" => Some(OptionValue::ILocVec(self.directions.clone())),
+            "black_directions" => self.black_directions.clone().map(OptionValue::ILocVec),
+            "capture" => Some(OptionValue::Bool(self.capture)),
+            "capture_only" => Some(OptionValue::Bool(self.capture_only)),
+            "first_move_only" => Some(OptionValue::Bool(self.first_move_only)),
+            "blockable" => Some(OptionValue::Bool(self.blockable)),
+            _ => None,
+        }
+    }
+
+    fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if self.capture_only && !self.capture {
+            problems.push(
+                "'capture_only' is set without 'capture', so the piece can never move.".to_string(),
+            );
+        }
+        if self.directions.contains(&ILoc(0, 0)) {
+            problems.push("A direction of (0, 0) doesn't move the piece.".to_string());
+        }
+        problems
     }
 }
@@ -2,20 +2,25 @@ use bit_vec::BitVec;
 use enum_dispatch::enum_dispatch;
 use serde::{Deserialize, Serialize};
 
+use crate::attributes::castle::Castle;
 use crate::attributes::enpassant::EnPassant;
 use crate::attributes::jumping::Jumping;
+use crate::attributes::promotion::Promotion;
 use crate::attributes::sliding::Sliding;
 use crate::board::Board;
+use crate::error::LinnyError;
 use crate::piece::{Color, ColorType, Piece, PieceType};
 use crate::util::{ILoc, Loc};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(not(feature = "bare"), derive(schemars::JsonSchema))]
-pub(crate) enum OptionType {
+pub enum OptionType {
     Bool,
     ILoc,
     ILocVec,
     DefaultPiece,
+    NumberVec,
+    PieceVec,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,37 +29,63 @@ pub(crate) enum OptionValue {
     ILoc(ILoc),
     ILocVec(Vec<ILoc>),
     DefaultPiece(PieceType),
+    NumberVec(Vec<usize>),
+    PieceVec(Vec<PieceType>),
 }
+fn bad_value(option: &str, expected: OptionType) -> LinnyError {
+    LinnyError::BadOptionValue {
+        option: option.to_string(),
+        expected,
+    }
+}
+
+/// Accessors for option values, erroring with the option's name if the value is of the wrong type
 impl OptionValue {
-    pub(crate) fn as_bool(&self) -> Option<bool> {
+    pub(crate) fn as_bool(&self, option: &str) -> Result<bool, LinnyError> {
         if let OptionValue::Bool(value) = self {
-            Some(*value)
+            Ok(*value)
         } else {
-            None
+            Err(bad_value(option, OptionType::Bool))
         }
     }
 
-    pub(crate) fn as_iloc(&self) -> Option<ILoc> {
+    pub(crate) fn as_iloc(&self, option: &str) -> Result<ILoc, LinnyError> {
         if let OptionValue::ILoc(value) = self {
-            Some(*value)
+            Ok(*value)
         } else {
-            None
+            Err(bad_value(option, OptionType::ILoc))
         }
     }
 
-    pub(crate) fn as_iloc_vec(&self) -> Option<Vec<ILoc>> {
+    pub(crate) fn as_iloc_vec(&self, option: &str) -> Result<Vec<ILoc>, LinnyError> {
         if let OptionValue::ILocVec(value) = self {
-            Some(value.clone())
+            Ok(value.clone())
         } else {
-            None
+            Err(bad_value(option, OptionType::ILocVec))
         }
     }
 
-    pub(crate) fn as_default_piece(&self) -> Option<PieceType> {
+    pub(crate) fn as_default_piece(&self, option: &str) -> Result<PieceType, LinnyError> {
         if let OptionValue::DefaultPiece(value) = self {
-            Some(*value)
+            Ok(*value)
         } else {
-            None
+            Err(bad_value(option, OptionType::DefaultPiece))
+        }
+    }
+
+    pub(crate) fn as_number_vec(&self, option: &str) -> Result<Vec<usize>, LinnyError> {
+        if let OptionValue::NumberVec(value) = self {
+            Ok(value.clone())
+        } else {
+            Err(bad_value(option, OptionType::NumberVec))
+        }
+    }
+
+    pub(crate) fn as_piece_vec(&self, option: &str) -> Result<Vec<PieceType>, LinnyError> {
+        if let OptionValue::PieceVec(value) = self {
+            Ok(value.clone())
+        } else {
+            Err(bad_value(option, OptionType::PieceVec))
         }
     }
 }
@@ -88,12 +119,15 @@ pub(crate) fn first_move_option() -> InfoOption {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) struct MoveData {
     pub(crate) to: Loc,
     pub(crate) capture: Option<Loc>,
     pub(crate) castle: Option<(Loc, Loc)>,
     pub(crate) piece: Piece,
+    /// What the piece turns into after moving
+    #[serde(default)]
+    pub(crate) promotion: Option<PieceType>,
 }
 
 #[enum_dispatch]
@@ -101,7 +135,13 @@ pub(crate) trait PieceAttributeTrait {
     fn moves(&self, board: &Board, piece: &Piece, moves: &mut Vec<MoveData>);
     fn attacks(&self, board: &Board, piece: &Piece, attacks: &mut BitVec);
     fn info(&self) -> PieceTraitInfo;
-    fn set_option(&mut self, name: &str, value: &Option<OptionValue>);
+    fn set_option(&mut self, name: &str, value: &Option<OptionValue>) -> Result<(), LinnyError>;
+    /// The current value of an option, None if it isn't set
+    fn get_option(&self, name: &str) -> Option<OptionValue>;
+    /// Problems with the options that their metadata can't describe, used by `Board::validate_piece`
+    fn problems(&self) -> Vec<String> {
+        vec![]
+    }
 }
 
 #[test]
@@ -126,13 +166,17 @@ pub(crate) enum PieceAttribute {
     Jumping,
     Sliding,
     EnPassant,
+    Castle,
+    Promotion,
 }
 impl PieceAttribute {
-    pub(crate) fn default_iter() -> [PieceAttribute; 3] {
+    pub(crate) fn default_iter() -> [PieceAttribute; 5] {
         [
             PieceAttribute::from(Jumping::default()),
             PieceAttribute::from(Sliding::default()),
             PieceAttribute::from(EnPassant::default()),
+            PieceAttribute::from(Castle::default()),
+            PieceAttribute::from(Promotion::default()),
         ]
     }
 }
@@ -145,3 +189,23 @@ pub(crate) fn bw<'a, T>(white: &'a T, black: &'a Option<T>, color: ColorType) ->
         white
     }
 }
+
+#[test]
+fn test_set_option() {
+    let mut jumping = PieceAttribute::from(Jumping::default());
+    jumping
+        .set_option("capture", &Some(OptionValue::Bool(true)))
+        .unwrap();
+    assert!(matches!(
+        jumping,
+        PieceAttribute::Jumping(Jumping { capture: true, .. })
+    ));
+
+    assert_eq!(
+        jumping.set_option("directions", &Some(OptionValue::Bool(true))),
+        Err(LinnyError::BadOptionValue {
+            option: "directions".to_string(),
+            expected: OptionType::ILocVec
+        })
+    );
+}
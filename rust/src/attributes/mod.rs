@@ -2,4 +2,5 @@ pub(crate) mod castle;
 pub(crate) mod enpassant;
 pub(crate) mod jumping;
 pub(crate) mod main;
+pub(crate) mod promotion;
 pub(crate) mod sliding;
@@ -0,0 +1,127 @@
+use bit_vec::BitVec;
+use serde::{Deserialize, Serialize};
+
+use crate::attributes::main::{
+    bw, InfoOption, MoveData, OptionType, OptionValue, PieceAttributeTrait, PieceTraitInfo,
+};
+use crate::board::Board;
+use crate::error::LinnyError;
+use crate::piece::{Color, Piece, PieceType};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(not(feature = "bare"), derive(schemars::JsonSchema))]
+pub(crate) struct Promotion {
+    /// Rows counted from the opponent's side of the board, `0` being the last row
+    pub(crate) rows: Vec<usize>,
+    pub(crate) black_rows: Option<Vec<usize>>,
+    pub(crate) pieces: Vec<PieceType>,
+    pub(crate) optional: bool,
+}
+impl Promotion {
+    /// Replaces every move that lands in the promotion zone with one move per promotion target
+    pub(crate) fn promote(&self, board: &Board, piece: &Piece, moves: &mut Vec<MoveData>) {
+        let rows = bw(&self.rows, &self.black_rows, piece.color);
+        let pieces = self
+            .pieces
+            .iter()
+            .filter(|piece_type| board.pieces.contains_key(piece_type))
+            .collect::<Vec<_>>();
+
+        let mut promoted = Vec::with_capacity(moves.len());
+        for move_data in moves.drain(..) {
+            let row = if piece.color == Color::WHITE {
+                move_data.to.1
+            } else {
+                board.height - 1 - move_data.to.1
+            };
+
+            if move_data.promotion.is_some()
+                || move_data.castle.is_some()
+                || !rows.contains(&row)
+                || pieces.is_empty()
+            {
+                promoted.push(move_data);
+                continue;
+            }
+
+            for piece_type in pieces.iter() {
+                promoted.push(MoveData {
+                    promotion: Some(**piece_type),
+                    ..move_data
+                });
+            }
+            if self.optional {
+                promoted.push(move_data);
+            }
+        }
+        *moves = promoted;
+    }
+}
+impl PieceAttributeTrait for Promotion {
+    /// Promotion only changes the moves of the other attributes, see `Promotion::promote`
+    fn moves(&self, _: &Board, _: &Piece, _: &mut Vec<MoveData>) {}
+
+    /// Promotion doesn't move the piece, so it does not attack anything
+    fn attacks(&self, _: &Board, _: &Piece, _: &mut BitVec) {}
+
+    fn info(&self) -> PieceTraitInfo {
+        PieceTraitInfo {
+            name: "Promotion",
+            description: "When the piece moves into one of the given rows, it turns into one of the given pieces.",
+            example: Some("Pawn promotion"),
+            options: vec![
+                InfoOption {
+                    optional: false,
+                    name: "rows",
+                    description: "The rows the piece promotes on, counted from the opponent's side of the board (0 is the last row).",
+                    options: OptionType::NumberVec,
+                    example: Some("Pawns promote on the last row."),
+                },
+                InfoOption {
+                    optional: true,
+                    name: "black_rows",
+                    description: "The rows the piece promotes on if the piece is black. If not provided, the white rows will be used.",
+                    options: OptionType::NumberVec,
+                    example: Some("Pawns promote on the last row."),
+                },
+                InfoOption {
+                    optional: false,
+                    name: "pieces",
+                    description: "The pieces the piece can promote to, including custom pieces.",
+                    options: OptionType::PieceVec,
+                    example: Some("Pawns can promote to a queen, rook, bishop or knight."),
+                },
+                InfoOption {
+                    optional: false,
+                    name: "optional",
+                    description: "The piece may choose to not promote.",
+                    options: OptionType::Bool,
+                    example: Some("Pawns in grand chess before the last row."),
+                },
+            ],
+        }
+    }
+
+    fn set_option(&mut self, name: &str, value: &Option<OptionValue>) -> Result<(), LinnyError> {
+        if let Some(value) = value {
+            match name {
+                "rows" => self.rows = value.as_number_vec(name)?,
+                "black_rows" => self.black_rows = Some(value.as_number_vec(name)?),
+                "pieces" => self.pieces = value.as_piece_vec(name)?,
+                "optional" => self.optional = value.as_bool(name)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn get_option(&self, name: &str) -> Option<OptionValue> {
+        match name {
+            "rows" => Some(OptionValue::NumberVec(self.rows.clone())),
+            "black_rows" => self.black_rows.clone().map(OptionValue::NumberVec),
+            "pieces" => Some(OptionValue::PieceVec(self.pieces.clone())),
+            "optional" => Some(OptionValue::Bool(self.optional)),
+            _ => None,
+        }
+    }
+}
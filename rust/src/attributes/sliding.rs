@@ -6,6 +6,7 @@ use crate::attributes::main::{
     PieceTraitInfo,
 };
 use crate::board::Board;
+use crate::error::LinnyError;
 use crate::piece::Piece;
 use crate::util::ILoc;
 
@@ -19,6 +20,10 @@ pub(crate) struct Sliding {
 }
 impl PieceAttributeTrait for Sliding {
     fn moves(&self, board: &Board, piece: &Piece, moves: &mut Vec<MoveData>) {
+        if self.first_move_only && !board.is_first_move(piece) {
+            return;
+        }
+
         let directions = bw(&self.directions, &self.black_directions, piece.color);
         for dir in directions {
             let mut try_loc = (piece.loc.as_iLoc() + *dir).try_as_loc();
@@ -35,6 +40,7 @@ impl PieceAttributeTrait for Sliding {
                         if color != piece.color {
                             moves.push(MoveData {
                                 castle: None,
+                                promotion: None,
                                 piece: *piece,
                                 to: loc,
                                 capture: Some(loc),
@@ -46,6 +52,7 @@ impl PieceAttributeTrait for Sliding {
 
                 moves.push(MoveData {
                     castle: None,
+                    promotion: None,
                     piece: *piece,
                     to: loc,
                     capture: None,
@@ -56,7 +63,7 @@ impl PieceAttributeTrait for Sliding {
     }
 
     fn attacks(&self, board: &Board, piece: &Piece, attacks: &mut BitVec) {
-        if !self.capture {
+        if !self.capture || (self.first_move_only && !board.is_first_move(piece)) {
             return;
         }
 
@@ -68,12 +75,10 @@ impl PieceAttributeTrait for Sliding {
                     break;
                 }
 
-                let occupied = board.check_loc(&loc);
-                if let Some(color) = occupied && color != piece.color {
-					let index = board.loc_as_bit(&loc);
-					attacks.set(index, true);
-					break;
-				}
+                attacks.set(board.loc_as_bit(&loc), true);
+                if board.check_loc(&loc).is_some() {
+                    break;
+                }
 
                 try_loc = (loc.as_iLoc() + *dir).try_as_loc();
             }
@@ -112,15 +117,34 @@ impl PieceAttributeTrait for Sliding {
         }
     }
 
-    fn set_option(&mut self, name: &str, value: &Option<OptionValue>) {
+    fn set_option(&mut self, name: &str, value: &Option<OptionValue>) -> Result<(), LinnyError> {
         if let Some(value) = value {
             match name {
-                "directions" => self.directions = value.as_iloc_vec().unwrap(),
-                "black_directions" => self.black_directions = Some(value.as_iloc_vec().unwrap()),
-                "capture" => self.capture = value.as_bool().unwrap(),
-                "first_move_only" => self.first_move_only = value.as_bool().unwrap(),
+                "directions" => self.directions = value.as_iloc_vec(name)?,
+                "black_directions" => self.black_directions = Some(value.as_iloc_vec(name)?),
+                "capture" => self.capture = value.as_bool(name)?,
+                "first_move_only" => self.first_move_only = value.as_bool(name)?,
                 _ => {}
             }
         }
+        Ok(())
+    }
+
+    fn get_option(&self, name: &str) -> Option<OptionValue> {
+        match name {
+            "directions" => Some(OptionValue::ILocVec(self.directions.clone())),
+            "black_directions" => self.black_directions.clone().map(OptionValue::ILocVec),
+            "capture" => Some(OptionValue::Bool(self.capture)),
+            "first_move_only" => Some(OptionValue::Bool(self.first_move_only)),
+            _ => None,
+        }
+    }
+
+    fn problems(&self) -> Vec<String> {
+        if self.directions.contains(&ILoc(0, 0)) {
+            vec!["A direction of (0, 0) would slide forever without moving.".to_string()]
+        } else {
+            vec![]
+        }
     }
 }
@@ -1,12 +1,15 @@
-use std::hash::{Hash, Hasher};
+use std::fmt;
 
 use bit_vec::BitVec;
-use rustc_hash::{FxHashMap, FxHasher};
+use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 
+use crate::attributes::jumping::Jumping;
 use crate::attributes::main::{MoveData, PieceAttribute};
 use crate::hashmap;
-use crate::piece::{default_pieces, Color, ColorType, Piece, PieceType};
+use crate::piece::{default_pieces, Color, ColorTrait, ColorType, Piece, PieceType};
+use crate::util::Loc;
+use crate::zobrist::ZobristKeys;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(not(feature = "bare"), derive(schemars::JsonSchema))]
@@ -17,6 +20,21 @@ pub(crate) struct PieceInfo {
     pub(crate) value: i32,
     pub(crate) image_path: [String; 2],
     pub(crate) attributes: Vec<PieceAttribute>,
+    /// If the piece must not be left under attack, like the king
+    #[serde(default)]
+    pub(crate) royal: bool,
+}
+
+impl PieceInfo {
+    /// Pawn-like pieces can't move back, so their moves reset the move rule
+    pub(crate) fn resets_move_rule(&self) -> bool {
+        self.attributes.iter().any(|attribute| {
+            matches!(
+                attribute,
+                PieceAttribute::Promotion(_) | PieceAttribute::EnPassant(_)
+            )
+        })
+    }
 }
 
 #[test]
@@ -25,6 +43,96 @@ fn schemas() {
     println!("PieceInfo:\n{}\n", serde_json::to_string(&schema).unwrap());
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum DrawReason {
+    /// The same position has occurred three times
+    Repetition,
+    /// No capture or pawn-like move has been made in the last `Board::move_rule` moves
+    MoveRule,
+    /// Neither side has enough material left to checkmate
+    InsufficientMaterial,
+}
+
+/// A way to win the game, set per variant with `Board::win_conditions`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "bare"), derive(schemars::JsonSchema))]
+pub(crate) enum WinCondition {
+    /// The opponent is in check and has no legal moves, a stalemate otherwise
+    Checkmate,
+    /// The opponent isn't in check but has no legal moves, a draw otherwise
+    Stalemate,
+    /// The opponent has nothing left but royal pieces, like in shatranj
+    BareKing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameResult::WhiteWins => write!(f, "1-0"),
+            GameResult::BlackWins => write!(f, "0-1"),
+            GameResult::Draw => write!(f, "1/2-1/2"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum GameStatus {
+    Ongoing,
+    /// The side to move is in check but has legal moves
+    Check,
+    /// The side to move is in check and has no legal moves, contains the winning color
+    Checkmate(ColorType),
+    /// The side to move is not in check but has no legal moves
+    Stalemate,
+    /// The game was won by a condition other than checkmate, contains the winning color
+    Won(ColorType, WinCondition),
+    Draw(DrawReason),
+}
+impl GameStatus {
+    /// The result of the game, if it is over
+    pub(crate) fn result(&self) -> Option<GameResult> {
+        match self {
+            GameStatus::Ongoing | GameStatus::Check => None,
+            GameStatus::Checkmate(Color::WHITE) | GameStatus::Won(Color::WHITE, _) => {
+                Some(GameResult::WhiteWins)
+            }
+            GameStatus::Checkmate(_) | GameStatus::Won(..) => Some(GameResult::BlackWins),
+            GameStatus::Stalemate | GameStatus::Draw(_) => Some(GameResult::Draw),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MoveError {
+    /// The moving piece does not belong to the side to move
+    WrongTurn,
+    /// The move is not one of the legal moves of the piece
+    IllegalMove,
+}
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::WrongTurn => write!(f, "It is not that piece's turn to move"),
+            MoveError::IllegalMove => write!(f, "That move is not legal"),
+        }
+    }
+}
+
+/// The state lost when a move is made, needed to take it back
+#[derive(Debug, Clone)]
+pub(crate) struct UndoData {
+    pub(crate) captured: Option<Piece>,
+    pub(crate) first_moves: [BitVec; 2],
+    pub(crate) half_move_clock: usize,
+    pub(crate) position_hash: u64,
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct Board {
     pub(crate) width: usize,
@@ -39,13 +147,38 @@ pub(crate) struct Board {
     pub(crate) first_moves: [BitVec; 2],
     /// Whats squares are under attack by a piece of a given color
     pub(crate) attacks: [BitVec; 2],
+    /// How many pieces of a given color attack each square, `attacks` is set where this isn't zero
+    pub(crate) attack_counts: [Vec<u32>; 2],
+    /// What squares each piece attacks, keyed by the square the piece is on
+    /// - `piece_attacks[color][index]`
+    pub(crate) piece_attacks: [FxHashMap<usize, BitVec>; 2],
     pub(crate) turn: ColorType,
     pub(crate) move_history: Vec<MoveData>,
+    /// What is needed to undo each move in `move_history`
+    pub(crate) undo_history: Vec<UndoData>,
     pub(crate) hashes: FxHashMap<u64, u8>,
+    pub(crate) zobrist: ZobristKeys,
+    /// Zobrist hash of the placement, kept up to date as pieces move
+    pub(crate) position_hash: u64,
+    /// Half moves since the last capture or pawn-like move
+    pub(crate) half_move_clock: usize,
+    /// How many full moves without a capture or pawn-like move draw the game, `None` to disable
+    pub(crate) move_rule: Option<usize>,
+    /// How the game can be won, by checkmate alone in standard chess
+    pub(crate) win_conditions: Vec<WinCondition>,
+    /// Rows, counted from each side's own first row, where pieces in a loaded fen can still make first move only moves
+    pub(crate) first_move_rows: Vec<usize>,
+    /// Half moves played before the position was loaded
+    pub(crate) start_half_moves: usize,
+    /// The square passed over and the piece that can be captured en passant in the loaded position
+    pub(crate) start_en_passant: Option<(Loc, Piece)>,
+    /// The id the next piece gets if its own is taken, never reused even if pieces are removed
+    pub(crate) next_piece_id: PieceType,
 }
 impl Board {
     pub(crate) fn new(width: usize, height: usize) -> Self {
         let pieces = default_pieces();
+        let next_piece_id = pieces.keys().max().map_or(0, |id| id + 1);
         let bitvec = BitVec::from_elem(width * height, false);
         let piece_location = {
             let mut map = hashmap! {};
@@ -56,6 +189,7 @@ impl Board {
         };
 
         Self {
+            zobrist: ZobristKeys::new(width * height, pieces.keys()),
             width,
             height,
             pieces,
@@ -63,10 +197,63 @@ impl Board {
             general_locations: [bitvec.clone(), bitvec.clone()],
             first_moves: [bitvec.clone(), bitvec.clone()],
             attacks: [bitvec.clone(), bitvec],
+            attack_counts: [vec![0; width * height], vec![0; width * height]],
+            piece_attacks: [hashmap! {}, hashmap! {}],
             turn: Color::WHITE,
             move_history: vec![],
+            undo_history: vec![],
             hashes: hashmap! {},
+            position_hash: 0,
+            half_move_clock: 0,
+            move_rule: Some(50),
+            win_conditions: vec![WinCondition::Checkmate],
+            first_move_rows: vec![1],
+            start_half_moves: 0,
+            start_en_passant: None,
+            next_piece_id,
+        }
+    }
+
+    /// Changes the size of the board, which removes every piece
+    pub(crate) fn resize(&mut self, width: usize, height: usize) {
+        let bitvec = BitVec::from_elem(width * height, false);
+        for color in [Color::WHITE, Color::BLACK] {
+            for locations in self.piece_locations[color].values_mut() {
+                *locations = bitvec.clone();
+            }
+        }
+        self.width = width;
+        self.height = height;
+        self.zobrist = ZobristKeys::new(width * height, self.pieces.keys());
+        self.clear();
+    }
+
+    /// Removes every piece and forgets the game history
+    pub(crate) fn clear(&mut self) {
+        let bitvec = BitVec::from_elem(self.bitlength(), false);
+        for color in [Color::WHITE, Color::BLACK] {
+            for locations in self.piece_locations[color].values_mut() {
+                locations.clear();
+            }
+            self.general_locations[color] = bitvec.clone();
+            self.first_moves[color] = bitvec.clone();
+            self.attacks[color] = bitvec.clone();
+            self.attack_counts[color] = vec![0; self.bitlength()];
+            self.piece_attacks[color].clear();
         }
+        self.turn = Color::WHITE;
+        self.move_history.clear();
+        self.undo_history.clear();
+        self.hashes.clear();
+        self.position_hash = 0;
+        self.half_move_clock = 0;
+        self.start_half_moves = 0;
+        self.start_en_passant = None;
+    }
+
+    /// Marks the piece on the given square as having moved
+    pub(crate) fn unset_first_move(&mut self, color: ColorType, index: usize) {
+        self.first_moves[color].set(index, false);
     }
 
     fn raw_raw_move(&mut self, from: usize, to: usize, piece: &Piece) {
@@ -77,53 +264,888 @@ impl Board {
         piece_locations.set(to, true);
         self.general_locations[piece.color].set(from, false);
         self.general_locations[piece.color].set(to, true);
+        self.position_hash ^= self.zobrist.piece(piece.color, piece.info_index, from)
+            ^ self.zobrist.piece(piece.color, piece.info_index, to);
+
+        self.unset_first_move(piece.color, from);
+        self.unset_first_move(piece.color, to);
     }
 
-    pub(crate) fn raw_move(&mut self, move_data: &MoveData) {
+    /// Turns the piece on the given square into another type
+    fn change_type(&mut self, index: usize, piece: &Piece, piece_type: PieceType) {
+        self.piece_locations[piece.color]
+            .get_mut(&piece.info_index)
+            .unwrap()
+            .set(index, false);
+        self.piece_locations[piece.color]
+            .get_mut(&piece_type)
+            .unwrap()
+            .set(index, true);
+        self.position_hash ^= self.zobrist.piece(piece.color, piece.info_index, index)
+            ^ self.zobrist.piece(piece.color, piece_type, index);
+    }
+
+    /// Makes a move without checking if it is legal, erroring if a piece it captures or castles with is missing
+    pub(crate) fn raw_move(&mut self, move_data: &MoveData) -> Result<UndoData, MoveError> {
+        // Look up every piece involved first, so a bad move leaves the board untouched
+        let captured = match &move_data.capture {
+            Some(capture) => Some(self.get(capture).ok_or(MoveError::IllegalMove)?),
+            None => None,
+        };
+        let castled = match &move_data.castle {
+            Some((from, _)) => Some(self.get(from).ok_or(MoveError::IllegalMove)?),
+            None => None,
+        };
+
         let from = self.loc_as_bit(&move_data.piece.loc);
         let to = self.loc_as_bit(&move_data.to);
+        let mut changed = vec![from, to];
+        let mut undo = UndoData {
+            captured: None,
+            first_moves: self.first_moves.clone(),
+            half_move_clock: self.half_move_clock,
+            position_hash: self.position_hash,
+        };
+
+        if move_data.capture.is_some() || move_data.piece.info(self).resets_move_rule() {
+            self.half_move_clock = 0;
+        } else {
+            self.half_move_clock += 1;
+        }
 
-        if let Some(capture) = &move_data.capture {
-            let capture_index = self.loc_as_bit(capture);
-            let piece = self.get(capture).unwrap();
+        if let Some(piece) = captured {
+            let capture_index = self.loc_as_bit(&piece.loc);
+            changed.push(capture_index);
+            undo.captured = Some(piece);
 
             self.general_locations[piece.color].set(capture_index, false);
             self.piece_locations[piece.color]
                 .get_mut(&piece.info_index)
                 .unwrap()
                 .set(capture_index, false);
+            self.position_hash ^= self
+                .zobrist
+                .piece(piece.color, piece.info_index, capture_index);
+            self.unset_first_move(piece.color, capture_index);
         }
 
-        if let Some((from, to)) = &move_data.castle {
-            let piece = self.get(from).unwrap();
-
+        if let Some(piece) = castled && let Some((from, to)) = &move_data.castle {
             let from = self.loc_as_bit(from);
             let to = self.loc_as_bit(to);
+            changed.extend([from, to]);
 
             self.raw_raw_move(from, to, &piece);
         }
 
         self.raw_raw_move(from, to, &move_data.piece);
+        if let Some(promotion) = move_data.promotion {
+            self.change_type(to, &move_data.piece, promotion);
+        }
+        self.refresh_attacks(&changed);
+        Ok(undo)
     }
 
-    pub(crate) fn move_piece(&mut self, move_data: &MoveData) {
-        self.raw_move(move_data);
+    /// Reverses `raw_move`, the move must be the last one made
+    pub(crate) fn raw_unmove(&mut self, move_data: &MoveData, undo: UndoData) {
+        let from = self.loc_as_bit(&move_data.piece.loc);
+        let to = self.loc_as_bit(&move_data.to);
+        let mut changed = vec![from, to];
+
+        if let Some(promotion) = move_data.promotion {
+            let promoted = Piece {
+                info_index: promotion,
+                ..move_data.piece
+            };
+            self.change_type(to, &promoted, move_data.piece.info_index);
+        }
+        self.raw_raw_move(to, from, &move_data.piece);
+
+        if let Some((from, to)) = &move_data.castle {
+            let piece = self.get(to).unwrap();
+
+            let from = self.loc_as_bit(from);
+            let to = self.loc_as_bit(to);
+            changed.extend([from, to]);
+
+            self.raw_raw_move(to, from, &piece);
+        }
+
+        if let Some(piece) = &undo.captured {
+            let capture_index = self.loc_as_bit(&piece.loc);
+            changed.push(capture_index);
+
+            self.general_locations[piece.color].set(capture_index, true);
+            self.piece_locations[piece.color]
+                .get_mut(&piece.info_index)
+                .unwrap()
+                .set(capture_index, true);
+        }
+
+        self.first_moves = undo.first_moves;
+        self.half_move_clock = undo.half_move_clock;
+        self.position_hash = undo.position_hash;
+        self.refresh_attacks(&changed);
+    }
+
+    /// Makes a move for the side to move, after checking that it is legal, then passes the turn
+    pub(crate) fn move_piece(&mut self, move_data: &MoveData) -> Result<(), MoveError> {
+        if move_data.piece.color != self.turn {
+            return Err(MoveError::WrongTurn);
+        }
+        if self.get(&move_data.piece.loc) != Some(move_data.piece)
+            || !move_data.piece.legal_moves(self).contains(move_data)
+        {
+            return Err(MoveError::IllegalMove);
+        }
+
+        let undo = self.raw_move(move_data)?;
         self.move_history.push(*move_data);
+        self.undo_history.push(undo);
+        self.turn = self.turn.other();
 
-        let hash_entry = self.hashes.entry(self.hash()).or_insert(0);
-        *hash_entry += 1;
+        self.record_position();
+
+        Ok(())
+    }
+
+    /// Takes back the last move made with `move_piece`, returning it
+    pub(crate) fn unmake_move(&mut self) -> Option<MoveData> {
+        let hash = self.hash();
+        let move_data = self.move_history.pop()?;
+        let undo = self.undo_history.pop()?;
+
+        if let Some(count) = self.hashes.get_mut(&hash) {
+            *count -= 1;
+            if *count == 0 {
+                self.hashes.remove(&hash);
+            }
+        }
+
+        self.raw_unmove(&move_data, undo);
+        self.turn = self.turn.other();
+        Some(move_data)
+    }
+
+    /// Recalculates what squares are under attack by every piece
+    pub(crate) fn update_attacks(&mut self) {
+        for color in [Color::WHITE, Color::BLACK] {
+            self.attacks[color] = BitVec::from_elem(self.bitlength(), false);
+            self.attack_counts[color] = vec![0; self.bitlength()];
+            self.piece_attacks[color].clear();
+            for piece in self.pieces_of(color) {
+                let attacks = piece.attacks(self);
+                self.count_attacks(color, &attacks, true);
+                self.piece_attacks[color].insert(self.loc_as_bit(&piece.loc), attacks);
+            }
+        }
+    }
+
+    /// Recalculates the attacks of pieces that are on, or attack, one of the changed squares, and of capturing blockable jumpers
+    ///
+    /// Sliding lines can only be opened or blocked on a square they attack, but a blockable jump passes over squares
+    /// missing from its map. Only these maps are taken out of and added back to the attacker counts
+    pub(crate) fn refresh_attacks(&mut self, changed: &[usize]) {
+        for color in [Color::WHITE, Color::BLACK] {
+            let mut stale = vec![];
+            for (index, attacks) in self.piece_attacks[color].iter() {
+                if changed.contains(index)
+                    || changed
+                        .iter()
+                        .any(|changed| attacks.get(*changed).contains(&true))
+                    || self.has_blockable_attacks(*index)
+                {
+                    stale.push(*index);
+                }
+            }
+            for index in changed.iter() {
+                if self.general_locations[color].get(*index).contains(&true)
+                    && !stale.contains(index)
+                {
+                    stale.push(*index);
+                }
+            }
+
+            for index in stale {
+                if let Some(attacks) = self.piece_attacks[color].remove(&index) {
+                    self.count_attacks(color, &attacks, false);
+                }
+                if let Some(piece) = self.get(&self.bit_as_loc(index)) && piece.color == color {
+                    let attacks = piece.attacks(self);
+                    self.count_attacks(color, &attacks, true);
+                    self.piece_attacks[color].insert(index, attacks);
+                }
+            }
+        }
+    }
+
+    /// Checks if the piece on a square attacks with jumps that can be blocked, which depend on squares missing from its attack map
+    fn has_blockable_attacks(&self, index: usize) -> bool {
+        self.get(&self.bit_as_loc(index)).map_or(false, |piece| {
+            piece.info(self).attributes.iter().any(|attribute| {
+                matches!(
+                    attribute,
+                    PieceAttribute::Jumping(Jumping {
+                        capture: true,
+                        blockable: true,
+                        ..
+                    })
+                )
+            })
+        })
+    }
+
+    /// Adds or takes away one attacker from every square in `attacks`, keeping `attacks[color]` in step
+    fn count_attacks(&mut self, color: ColorType, attacks: &BitVec, add: bool) {
+        for (index, bit) in attacks.iter().enumerate() {
+            if !bit {
+                continue;
+            }
+            let count = &mut self.attack_counts[color][index];
+            if add {
+                *count += 1;
+            } else {
+                *count -= 1;
+            }
+            self.attacks[color].set(index, *count > 0);
+        }
+    }
+
+    /// Checks if any royal piece of the given color is under attack, requires `attacks` to be up to date
+    pub(crate) fn in_check(&self, color: ColorType) -> bool {
+        self.pieces_of(color).iter().any(|piece| {
+            piece.info(self).royal
+                && self.attacks[color.other()]
+                    .get(self.loc_as_bit(&piece.loc))
+                    .contains(&true)
+        })
+    }
+
+    /// Checks if a move does not leave a royal piece of the moving color under attack
+    pub(crate) fn is_legal(&self, move_data: &MoveData) -> bool {
+        self.clone().try_move(move_data)
+    }
+
+    /// Makes and unmakes a move, returning if it left the moving color out of check
+    pub(crate) fn try_move(&mut self, move_data: &MoveData) -> bool {
+        let Ok(undo) = self.raw_move(move_data) else {
+            return false;
+        };
+        let legal = !self.in_check(move_data.piece.color);
+        self.raw_unmove(move_data, undo);
+        legal
+    }
+
+    /// All moves of a given color that do not leave a royal piece under attack
+    pub(crate) fn legal_moves(&self, color: ColorType) -> Vec<MoveData> {
+        let mut scratch = self.clone();
+        let mut moves = vec![];
+        for piece in self.pieces_of(color) {
+            moves.extend(
+                piece
+                    .moves(self)
+                    .into_iter()
+                    .filter(|move_data| scratch.try_move(move_data)),
+            );
+        }
+        moves
+    }
+
+    /// Counts the positions reached by playing every legal move sequence of the given length
+    ///
+    /// Games aren't stopped by draws or win conditions, so the counts can be compared with other engines
+    pub(crate) fn perft(&mut self, depth: usize) -> u64 {
+        let moves = self.legal_moves(self.turn);
+        if depth <= 1 {
+            return if depth == 0 { 1 } else { moves.len() as u64 };
+        }
+
+        let mut nodes = 0;
+        for move_data in moves {
+            let undo = self.raw_move(&move_data).unwrap();
+            self.move_history.push(move_data);
+            self.undo_history.push(undo);
+            self.turn = self.turn.other();
+
+            nodes += self.perft(depth - 1);
+
+            self.turn = self.turn.other();
+            self.move_history.pop();
+            let undo = self.undo_history.pop().unwrap();
+            self.raw_unmove(&move_data, undo);
+        }
+        nodes
     }
 
+    /// The state of the game for the side to move
+    pub(crate) fn status(&self) -> GameStatus {
+        let in_check = self.in_check(self.turn);
+        let winner = self.turn.other();
+        if self.legal_moves(self.turn).is_empty() {
+            return if in_check && self.win_conditions.contains(&WinCondition::Checkmate) {
+                GameStatus::Checkmate(winner)
+            } else if !in_check && self.win_conditions.contains(&WinCondition::Stalemate) {
+                GameStatus::Won(winner, WinCondition::Stalemate)
+            } else {
+                GameStatus::Stalemate
+            };
+        }
+        if self.win_conditions.contains(&WinCondition::BareKing)
+            && self.is_bare(self.turn)
+            && !self.is_bare(winner)
+        {
+            return GameStatus::Won(winner, WinCondition::BareKing);
+        }
+
+        if self.hashes.get(&self.hash()).copied().unwrap_or(0) >= 3 {
+            return GameStatus::Draw(DrawReason::Repetition);
+        }
+        if let Some(move_rule) = self.move_rule && self.half_move_clock >= move_rule * 2 {
+            return GameStatus::Draw(DrawReason::MoveRule);
+        }
+        if self.insufficient_material() {
+            return GameStatus::Draw(DrawReason::InsufficientMaterial);
+        }
+
+        if in_check {
+            GameStatus::Check
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+
+    /// Checks if a color has nothing left but royal pieces
+    pub(crate) fn is_bare(&self, color: ColorType) -> bool {
+        self.pieces_of(color)
+            .iter()
+            .all(|piece| piece.info(self).royal)
+    }
+
+    /// The most material, by `PieceInfo.value`, that can't force a checkmate, like a single bishop or knight
+    pub(crate) const INSUFFICIENT_MATERIAL: i32 = 3;
+
+    /// Checks if there are no pawn-like pieces and the non-royal material left is at most `INSUFFICIENT_MATERIAL`
+    pub(crate) fn insufficient_material(&self) -> bool {
+        let mut material = 0;
+        for color in [Color::WHITE, Color::BLACK] {
+            for piece in self.pieces_of(color) {
+                let info = piece.info(self);
+                if info.resets_move_rule() {
+                    return false;
+                }
+                if !info.royal {
+                    material += info.value;
+                }
+            }
+        }
+        material <= Self::INSUFFICIENT_MATERIAL
+    }
+
+    /// Hashes the position, which is the placement, side to move, castling rights and en passant target
     pub(crate) fn hash(&self) -> u64 {
-        let mut hash = FxHasher::default();
-        self.general_locations.hash(&mut hash);
-        for test in self.piece_locations.iter() {
-            for (piece_type, bitvec) in test.iter() {
-                piece_type.hash(&mut hash);
-                bitvec.hash(&mut hash);
+        let mut hash = self.position_hash ^ self.castling_hash();
+        if self.turn == Color::BLACK {
+            hash ^= self.zobrist.black_to_move;
+        }
+        if let Some(target) = self.en_passant_target() {
+            hash ^= self.zobrist.en_passant[self.loc_as_bit(&target.loc)];
+        }
+        hash
+    }
+
+    /// Hashes the castling rights, as the squares of unmoved castling pieces and the unmoved rooks they can castle with
+    ///
+    /// Other pieces that haven't moved are left out, so a knight that leaves and comes back repeats the position
+    pub(crate) fn castling_hash(&self) -> u64 {
+        let mut hash = 0;
+        for color in [Color::WHITE, Color::BLACK] {
+            let mut squares = vec![];
+            for piece in self.pieces_of(color) {
+                if !self.has_castle(&piece) || !self.is_first_move(&piece) {
+                    continue;
+                }
+                for (rook, _) in self.castle_rooks(&piece) {
+                    if self.is_first_move(&rook) {
+                        squares.extend([piece.loc, rook.loc]);
+                    }
+                }
             }
+            squares.sort_by_key(|loc| (loc.1, loc.0));
+            squares.dedup();
+            for loc in squares {
+                hash ^= self.zobrist.castling[color][self.loc_as_bit(&loc)];
+            }
+        }
+        hash
+    }
+
+    /// Recalculates `position_hash` from scratch
+    pub(crate) fn full_position_hash(&self) -> u64 {
+        let mut hash = 0;
+        for color in [Color::WHITE, Color::BLACK] {
+            for piece in self.pieces_of(color) {
+                let index = self.loc_as_bit(&piece.loc);
+                hash ^= self.zobrist.piece(color, piece.info_index, index);
+            }
+        }
+        hash
+    }
+
+    /// Counts the current position towards repetitions
+    pub(crate) fn record_position(&mut self) {
+        let hash_entry = self.hashes.entry(self.hash()).or_insert(0);
+        *hash_entry += 1;
+    }
+}
+
+#[test]
+fn test_legal_moves() {
+    let mut board = Board::new(8, 8);
+    board.load_fen(Board::DEFAULT_FEN).unwrap();
+    assert_eq!(board.legal_moves(Color::WHITE).len(), 20);
+
+    // Pinned rook can only move along the pin, king can't step onto the e file
+    let mut board = Board::new(8, 8);
+    board.load_fen("4r3/8/8/8/8/8/4R3/4K3").unwrap();
+    let moves = board.legal_moves(Color::WHITE);
+    assert!(moves
+        .iter()
+        .filter(|move_data| move_data.piece.info_index == crate::piece::DefaultPiece::ROOK)
+        .all(|move_data| move_data.to.0 == 4));
+    assert_eq!(moves.len(), 10);
+}
+
+#[test]
+fn test_first_moves() {
+    let mut board = Board::new(8, 8);
+    board.load_fen(Board::DEFAULT_FEN).unwrap();
+
+    let pawn = board.get(&Loc(4, 6)).unwrap();
+    assert!(board.is_first_move(&pawn));
+    assert_eq!(pawn.moves(&board).len(), 2);
+
+    let single_step = pawn
+        .moves(&board)
+        .into_iter()
+        .find(|move_data| move_data.to == Loc(4, 5))
+        .unwrap();
+    board.move_piece(&single_step).unwrap();
+
+    let pawn = board.get(&Loc(4, 5)).unwrap();
+    assert!(!board.is_first_move(&pawn));
+    assert_eq!(pawn.moves(&board).len(), 1);
+
+    // A piece right in front stops the double step, not only one on the square it lands on
+    let mut board = Board::new(8, 8);
+    board.load_fen("4k3/8/8/8/8/4n3/4P3/4K3").unwrap();
+    let pawn = board.get(&Loc(4, 6)).unwrap();
+    assert!(board.is_first_move(&pawn));
+    assert!(pawn.moves(&board).is_empty());
+    let mut board = Board::new(8, 8);
+    board.load_fen("4k3/8/8/8/4n3/8/4P3/4K3").unwrap();
+    let pawn = board.get(&Loc(4, 6)).unwrap();
+    assert_eq!(pawn.moves(&board).len(), 1);
+}
+
+#[test]
+fn test_castling() {
+    let mut board = Board::new(8, 8);
+    board
+        .load_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+        .unwrap();
+    assert_eq!(board.legal_moves(Color::WHITE).len(), 26);
+
+    let king = board.get(&Loc(4, 7)).unwrap();
+    let castles = king
+        .legal_moves(&board)
+        .into_iter()
+        .filter(|move_data| move_data.castle.is_some())
+        .collect::<Vec<_>>();
+    assert_eq!(castles.len(), 2);
+
+    let kingside = castles
+        .iter()
+        .find(|move_data| move_data.to == Loc(6, 7))
+        .unwrap();
+    assert_eq!(kingside.castle, Some((Loc(7, 7), Loc(5, 7))));
+    board.move_piece(kingside).unwrap();
+    assert_eq!(
+        board.get(&Loc(5, 7)).unwrap().info_index,
+        crate::piece::DefaultPiece::ROOK
+    );
+    board.unmake_move();
+    assert_eq!(
+        board.get(&Loc(7, 7)).unwrap().info_index,
+        crate::piece::DefaultPiece::ROOK
+    );
+
+    // Can't castle through an attacked square or once the rook has moved
+    let mut board = Board::new(8, 8);
+    board
+        .load_fen("r3k2r/8/8/8/8/8/5q2/R3K2R w Kkq - 0 1")
+        .unwrap();
+    let king = board.get(&Loc(4, 7)).unwrap();
+    assert!(king
+        .moves(&board)
+        .iter()
+        .all(|move_data| move_data.castle.is_none()));
+
+    // Only pieces of the partner type can be castled with
+    let mut board = Board::new(8, 8);
+    board.load_fen("4k3/8/8/8/8/8/8/N3K2B w KQ - 0 1").unwrap();
+    let castles = |board: &Board| {
+        board
+            .legal_moves(Color::WHITE)
+            .into_iter()
+            .filter(|move_data| move_data.castle.is_some())
+            .count()
+    };
+    assert_eq!(castles(&board), 0);
+    assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/N3K2B w - - 0 1");
+
+    let king = board
+        .pieces
+        .get_mut(&crate::piece::DefaultPiece::KING)
+        .unwrap();
+    for attribute in king.attributes.iter_mut() {
+        if let PieceAttribute::Castle(castle) = attribute {
+            castle.rook_piece = crate::piece::DefaultPiece::BISHOP;
         }
-        self.half_moves().hash(&mut hash);
-        hash.finish()
     }
+    board.load_fen("4k3/8/8/8/8/8/8/N3K2B w KQ - 0 1").unwrap();
+    assert_eq!(castles(&board), 1);
+    assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/N3K2B w K - 0 1");
+}
+
+#[test]
+fn test_en_passant() {
+    let find = |board: &Board, from: Loc, to: Loc| {
+        board
+            .get(&from)
+            .unwrap()
+            .legal_moves(board)
+            .into_iter()
+            .find(|move_data| move_data.to == to)
+    };
+
+    let mut board = Board::new(8, 8);
+    board.load_fen("4k3/3p4/8/4P3/8/8/8/4K3").unwrap();
+    board.turn = Color::BLACK;
+
+    // Right after the double step
+    let double_step = find(&board, Loc(3, 1), Loc(3, 3)).unwrap();
+    board.move_piece(&double_step).unwrap();
+    let en_passant = find(&board, Loc(4, 3), Loc(3, 2)).unwrap();
+    assert_eq!(en_passant.capture, Some(Loc(3, 3)));
+
+    // Not after another move has been made
+    let king = find(&board, Loc(4, 7), Loc(4, 6)).unwrap();
+    board.move_piece(&king).unwrap();
+    let king = find(&board, Loc(4, 0), Loc(4, 1)).unwrap();
+    board.move_piece(&king).unwrap();
+    assert!(find(&board, Loc(4, 3), Loc(3, 2)).is_none());
+
+    // Not after two single steps
+    let mut board = Board::new(8, 8);
+    board.load_fen("4k3/8/3p4/4P3/8/8/8/4K3").unwrap();
+    board.turn = Color::BLACK;
+    let single_step = find(&board, Loc(3, 2), Loc(3, 3)).unwrap();
+    board.move_piece(&single_step).unwrap();
+    assert!(find(&board, Loc(4, 3), Loc(3, 2)).is_none());
+}
+
+#[test]
+fn test_promotion() {
+    let mut board = Board::new(8, 8);
+    board.load_fen("k7/4P3/8/8/8/8/8/7K").unwrap();
+    let pawn = board.get(&Loc(4, 1)).unwrap();
+    let moves = pawn.legal_moves(&board);
+    assert_eq!(moves.len(), 4);
+
+    let queen = moves
+        .iter()
+        .find(|move_data| move_data.promotion == Some(crate::piece::DefaultPiece::QUEEN))
+        .unwrap();
+    board.move_piece(queen).unwrap();
+    assert_eq!(
+        board.get(&Loc(4, 0)).unwrap().info_index,
+        crate::piece::DefaultPiece::QUEEN
+    );
+    assert!(board.in_check(Color::BLACK));
+
+    board.unmake_move();
+    assert_eq!(board.get(&Loc(4, 1)), Some(pawn));
+    assert!(board.get(&Loc(4, 0)).is_none());
+}
+
+#[test]
+fn test_draws() {
+    let mut board = Board::new(8, 8);
+    board.load_fen("k7/8/8/8/8/8/8/6NK").unwrap();
+    assert_eq!(
+        board.status(),
+        GameStatus::Draw(DrawReason::InsufficientMaterial)
+    );
+
+    // Shuffle the rooks back and forth, neither can castle so the starting position counts too
+    let mut board = Board::new(8, 8);
+    board.load_fen("1k5r/8/8/8/8/8/8/R5K1").unwrap();
+    let shuffle = [
+        (Loc(0, 7), Loc(0, 6)),
+        (Loc(7, 0), Loc(7, 1)),
+        (Loc(0, 6), Loc(0, 7)),
+        (Loc(7, 1), Loc(7, 0)),
+    ];
+    for (i, (from, to)) in shuffle.iter().cycle().take(8).enumerate() {
+        assert_eq!(board.status().result(), None, "move {i}");
+        let move_data = board
+            .get(from)
+            .unwrap()
+            .legal_moves(&board)
+            .into_iter()
+            .find(|move_data| move_data.to == *to)
+            .unwrap();
+        board.move_piece(&move_data).unwrap();
+    }
+    assert_eq!(board.status(), GameStatus::Draw(DrawReason::Repetition));
+    assert_eq!(board.status().result(), Some(GameResult::Draw));
+    assert_eq!(board.half_move_clock, 8);
+
+    board.unmake_move();
+    assert_eq!(board.status(), GameStatus::Ongoing);
+    board.move_rule = Some(3);
+    assert_eq!(board.status(), GameStatus::Draw(DrawReason::MoveRule));
+
+    // Knights leaving and coming back repeat the starting position, castling rights are untouched
+    let mut board = Board::new(8, 8);
+    board.load_fen(Board::DEFAULT_FEN).unwrap();
+    for notation in ["g1f3", "g8f6", "f3g1", "f6g8"].iter().cycle().take(8) {
+        assert_eq!(board.status(), GameStatus::Ongoing);
+        board
+            .move_piece(&board.parse_move(notation).unwrap())
+            .unwrap();
+    }
+    assert_eq!(board.hashes[&board.hash()], 3);
+    assert_eq!(board.status(), GameStatus::Draw(DrawReason::Repetition));
+
+    // A rook that leaves and comes back has lost its castling right, so it doesn't repeat the position
+    board
+        .load_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+        .unwrap();
+    let start = board.hash();
+    for notation in ["h1h2", "h8h7", "h2h1", "h7h8"] {
+        board
+            .move_piece(&board.parse_move(notation).unwrap())
+            .unwrap();
+    }
+    assert_ne!(board.hash(), start);
+    assert_eq!(board.to_fen(), "r3k2r/8/8/8/8/8/8/R3K2R w Qq - 4 3");
+}
+
+#[test]
+fn test_move_validation() {
+    let mut board = Board::new(8, 8);
+    board.load_fen(Board::DEFAULT_FEN).unwrap();
+
+    let black_pawn = board.get(&Loc(4, 1)).unwrap();
+    let black_move = black_pawn.moves(&board)[0];
+    assert_eq!(board.move_piece(&black_move), Err(MoveError::WrongTurn));
+
+    let rook = board.get(&Loc(0, 7)).unwrap();
+    let blocked = MoveData {
+        to: Loc(0, 4),
+        capture: None,
+        castle: None,
+        promotion: None,
+        piece: rook,
+    };
+    assert_eq!(board.move_piece(&blocked), Err(MoveError::IllegalMove));
+    assert_eq!(board.turn, Color::WHITE);
+
+    let pawn = board.get(&Loc(4, 6)).unwrap();
+    board.move_piece(&pawn.moves(&board)[0]).unwrap();
+    assert_eq!(board.turn, Color::BLACK);
+    assert_eq!(board.move_piece(&black_move), Ok(()));
+    assert_eq!(board.turn, Color::WHITE);
+}
+
+#[test]
+fn test_unmake_move() {
+    let mut board = Board::new(8, 8);
+    board
+        .load_fen("r3k2r/pppq1ppp/2n2n2/3pp3/1b1PP1b1/2N2N2/PPPQ1PPP/R3KB1R")
+        .unwrap();
+    let original = board.clone();
+
+    for _ in 0..10 {
+        let moves = board.legal_moves(board.turn);
+        let capture = moves.iter().find(|move_data| move_data.capture.is_some());
+        board.move_piece(capture.unwrap_or(&moves[0])).unwrap();
+    }
+    while board.unmake_move().is_some() {}
+
+    assert_eq!(board.piece_locations, original.piece_locations);
+    assert_eq!(board.general_locations, original.general_locations);
+    assert_eq!(board.first_moves, original.first_moves);
+    assert_eq!(board.attacks, original.attacks);
+    assert_eq!(board.hashes, original.hashes);
+    assert_eq!(board.turn, original.turn);
+    assert_eq!(board.hash(), original.hash());
+}
+
+#[test]
+fn test_zobrist() {
+    let mut board = Board::new(8, 8);
+    board
+        .load_fen("r3k2r/pPpq1ppp/2n2n2/3pp3/1b1PP1b1/2N2N2/PPPQ1PPP/R3KB1R")
+        .unwrap();
+    assert_eq!(board.position_hash, board.full_position_hash());
+
+    let mut seen = vec![board.hash()];
+    for i in 0..20 {
+        let moves = board.legal_moves(board.turn);
+        let capture = moves.iter().find(|move_data| move_data.capture.is_some());
+        board
+            .move_piece(capture.unwrap_or(&moves[i % moves.len()]))
+            .unwrap();
+
+        assert_eq!(board.position_hash, board.full_position_hash());
+        assert!(!seen.contains(&board.hash()));
+        seen.push(board.hash());
+    }
+
+    // Transpositions get the same key, whatever the pieces that can't castle did on the way
+    let play = |moves: &[&str]| {
+        let mut board = Board::new(8, 8);
+        board.load_fen(Board::DEFAULT_FEN).unwrap();
+        for notation in moves {
+            board
+                .move_piece(&board.parse_move(notation).unwrap())
+                .unwrap();
+        }
+        board.hash()
+    };
+    assert_eq!(
+        play(&["g1f3", "g8f6", "f3g1", "f6g8", "e2e4", "e7e5"]),
+        play(&["e2e4", "e7e5"])
+    );
+    assert_ne!(
+        play(&["e2e4", "e7e5", "e1e2", "e8e7", "e2e1", "e7e8", "g1f3", "g8f6"]),
+        play(&["e2e4", "e7e5", "g1f3", "g8f6"])
+    );
+}
+
+#[test]
+fn test_status() {
+    let mut board = Board::new(8, 8);
+    board.load_fen(Board::DEFAULT_FEN).unwrap();
+    assert_eq!(board.status(), GameStatus::Ongoing);
+
+    // Back rank mate
+    let mut board = Board::new(8, 8);
+    board.load_fen("6k1/8/8/8/8/8/5PPP/r5K1").unwrap();
+    assert_eq!(board.status(), GameStatus::Checkmate(Color::BLACK));
+
+    let mut board = Board::new(8, 8);
+    board.load_fen("6k1/8/8/8/8/8/5PP1/r5K1").unwrap();
+    assert_eq!(board.status(), GameStatus::Check);
+
+    let mut board = Board::new(8, 8);
+    board.load_fen("k7/8/8/8/8/8/2q5/K7").unwrap();
+    assert_eq!(board.status(), GameStatus::Stalemate);
+    board.win_conditions.push(WinCondition::Stalemate);
+    assert_eq!(
+        board.status(),
+        GameStatus::Won(Color::BLACK, WinCondition::Stalemate)
+    );
+
+    board.load_fen("k7/8/8/8/8/8/7q/K7").unwrap();
+    assert_eq!(board.status(), GameStatus::Ongoing);
+    board.win_conditions.push(WinCondition::BareKing);
+    assert_eq!(
+        board.status(),
+        GameStatus::Won(Color::BLACK, WinCondition::BareKing)
+    );
+    assert_eq!(board.status().result(), Some(GameResult::BlackWins));
+}
+
+#[test]
+fn test_perft() {
+    let mut board = Board::new(8, 8);
+    board.load_fen(Board::DEFAULT_FEN).unwrap();
+    assert_eq!(board.perft(1), 20);
+    assert_eq!(board.perft(3), 8902);
+
+    // Castling, en passant and promotions
+    board
+        .load_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+        .unwrap();
+    assert_eq!(board.perft(2), 2039);
+    board
+        .load_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1")
+        .unwrap();
+    assert_eq!(board.perft(3), 2812);
+}
+
+#[test]
+fn test_incremental_attacks() {
+    use crate::piece::DefaultPiece;
+    use crate::util::ILoc;
+
+    /// Walks every move sequence like `perft`, checking the attacks against a full recalculation after each move and unmove
+    fn walk(board: &mut Board, depth: usize) {
+        let check = |board: &Board| {
+            let mut fresh = board.clone();
+            fresh.update_attacks();
+            assert_eq!(board.attacks, fresh.attacks);
+            assert_eq!(board.attack_counts, fresh.attack_counts);
+        };
+        if depth == 0 {
+            return;
+        }
+        for move_data in board.legal_moves(board.turn) {
+            let undo = board.raw_move(&move_data).unwrap();
+            board.move_history.push(move_data);
+            board.undo_history.push(undo);
+            board.turn = board.turn.other();
+            check(board);
+            walk(board, depth - 1);
+
+            board.turn = board.turn.other();
+            board.move_history.pop();
+            let undo = board.undo_history.pop().unwrap();
+            board.raw_unmove(&move_data, undo);
+            check(board);
+        }
+    }
+
+    let mut board = Board::new(8, 8);
+    board
+        .load_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+        .unwrap();
+    walk(&mut board, 2);
+    board
+        .load_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1")
+        .unwrap();
+    walk(&mut board, 3);
+
+    // A blockable jump attacks past a square it doesn't attack, so a piece moving there still blocks it
+    let mut board = Board::new(8, 8);
+    board
+        .pieces
+        .get_mut(&DefaultPiece::BISHOP)
+        .unwrap()
+        .attributes = vec![PieceAttribute::Jumping(Jumping {
+        directions: vec![ILoc(0, -2)],
+        capture: true,
+        blockable: true,
+        ..Default::default()
+    })];
+    board.load_fen("k7/8/8/8/8/8/8/K3B1N1 w - - 0 1").unwrap();
+    let e3 = board.loc_as_bit(&Loc(4, 5));
+    assert!(board.attacks[Color::WHITE].get(e3).unwrap());
+    board
+        .move_piece(&board.parse_move("g1e2").unwrap())
+        .unwrap();
+    assert!(!board.attacks[Color::WHITE].get(e3).unwrap());
+    walk(&mut board, 2);
 }
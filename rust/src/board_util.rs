@@ -3,28 +3,43 @@ use ordinal::Ordinal;
 use serde::{Deserialize, Serialize};
 
 use crate::attributes::main::PieceAttribute;
-use crate::board::{Board, PieceInfo};
-use crate::piece::{Color, ColorType, DefaultPiece, Piece};
-use crate::util::Loc;
+use crate::board::{Board, GameStatus, PieceInfo};
+use crate::error::LinnyError;
+use crate::piece::{Color, ColorType, Piece, PieceType};
+use crate::util::{Loc, NotationError};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct CpgnMetadata {
-    pub(crate) white: Option<String>,
-    pub(crate) black: Option<String>,
-    pub(crate) result: Option<String>,
-    pub(crate) reason: Option<String>,
+pub(crate) struct CombinedPiece {
+    pub(crate) piece: Piece,
+    pub(crate) info: PieceInfo,
+    /// Where the piece is in notation, like "e4"
+    pub(crate) square: String,
 }
 
+/// Everything the frontend needs to draw the board
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct Cpgn {
-    pub(crate) metadata: CpgnMetadata,
-    pub(crate) moves: String,
+pub(crate) struct BoardView {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) turn: ColorType,
+    pub(crate) status: GameStatus,
+    /// Every piece on the board, row by row from the top left
+    pub(crate) pieces: Vec<CombinedPiece>,
+    /// The last move in uci style, for highlighting
+    pub(crate) last_move: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct CombinedPiece {
-    pub(crate) piece: Piece,
-    pub(crate) info: PieceInfo,
+/// Converts notation like "e4" to a location on a board of the given size, with the first row at the bottom
+pub(crate) fn notation_to_loc(
+    notation: &str,
+    width: usize,
+    height: usize,
+) -> Result<Loc, NotationError> {
+    let loc = Loc::from_notation(notation)?;
+    if loc.0 >= width || loc.1 >= height {
+        return Err(NotationError::OutOfBounds(notation.to_string()));
+    }
+    Ok(Loc(loc.0, height - 1 - loc.1))
 }
 
 impl Board {
@@ -46,8 +61,24 @@ impl Board {
         loc.0 < self.width && loc.1 < self.height
     }
 
+    /// Converts a location to notation like "e4", with the first row at the bottom of the board
+    pub(crate) fn loc_to_notation(&self, loc: &Loc) -> String {
+        Loc(loc.0, self.height - 1 - loc.1).as_notation()
+    }
+
+    /// Converts notation like "e4" or "b10" to a location on this board
+    pub(crate) fn notation_to_loc(&self, notation: &str) -> Result<Loc, NotationError> {
+        notation_to_loc(notation, self.width, self.height)
+    }
+
+    pub(crate) fn piece_info(&self, piece_type: PieceType) -> Result<&PieceInfo, LinnyError> {
+        self.pieces
+            .get(&piece_type)
+            .ok_or(LinnyError::UnknownPieceType(piece_type))
+    }
+
     pub(crate) fn half_moves(&self) -> usize {
-        self.move_history.len()
+        self.start_half_moves + self.move_history.len()
     }
 
     pub(crate) fn full_moves(&self) -> usize {
@@ -69,6 +100,51 @@ impl Board {
         None
     }
 
+    /// Checks if the piece hasn't moved yet
+    pub(crate) fn is_first_move(&self, piece: &Piece) -> bool {
+        self.first_moves[piece.color]
+            .get(self.loc_as_bit(&piece.loc))
+            .contains(&true)
+    }
+
+    /// The piece that made the last move, if it was a first move of more than one square that can be captured en passant
+    pub(crate) fn en_passant_target(&self) -> Option<Piece> {
+        let target = self.last_multi_step()?;
+        self.capturable_en_passant(target.info_index)
+            .then_some(target)
+    }
+
+    /// Checks if any piece can capture pieces of the given type en passant
+    pub(crate) fn capturable_en_passant(&self, piece_type: PieceType) -> bool {
+        self.pieces.values().any(|info| {
+            info.attributes.iter().any(|attribute| {
+                matches!(attribute, PieceAttribute::EnPassant(en_passant) if en_passant.piece == piece_type)
+            })
+        })
+    }
+
+    fn last_multi_step(&self) -> Option<Piece> {
+        let Some(last) = self.move_history.last() else {
+            return self.start_en_passant.map(|(_, piece)| piece);
+        };
+        let undo = self.undo_history.last()?;
+
+        let from = last.piece.loc;
+        let distance = from.0.abs_diff(last.to.0).max(from.1.abs_diff(last.to.1));
+        let first_move = undo.first_moves[last.piece.color]
+            .get(self.loc_as_bit(&from))
+            .contains(&true);
+
+        if distance > 1 && first_move {
+            Some(Piece {
+                loc: last.to,
+                ..last.piece
+            })
+        } else {
+            None
+        }
+    }
+
     pub(crate) fn get(&self, loc: &Loc) -> Option<Piece> {
         if !self.valid_loc(loc) {
             return None;
@@ -90,6 +166,47 @@ impl Board {
         None
     }
 
+    /// Returns every piece of a given color
+    pub(crate) fn pieces_of(&self, color: ColorType) -> Vec<Piece> {
+        let mut pieces = vec![];
+        for (info_index, map) in self.piece_locations[color].iter() {
+            for (index, bit) in map.iter().enumerate() {
+                if bit {
+                    pieces.push(Piece {
+                        color,
+                        info_index: *info_index,
+                        loc: self.bit_as_loc(index),
+                    });
+                }
+            }
+        }
+        pieces
+    }
+
+    pub(crate) fn view(&self) -> BoardView {
+        let mut pieces = [Color::WHITE, Color::BLACK]
+            .iter()
+            .flat_map(|color| self.pieces_of(*color))
+            .collect::<Vec<_>>();
+        pieces.sort_by_key(|piece| (piece.loc.1, piece.loc.0));
+
+        BoardView {
+            width: self.width,
+            height: self.height,
+            turn: self.turn,
+            status: self.status(),
+            pieces: pieces
+                .into_iter()
+                .map(|piece| CombinedPiece {
+                    info: piece.info(self).clone(),
+                    square: self.loc_to_notation(&piece.loc),
+                    piece,
+                })
+                .collect(),
+            last_move: self.move_history.last().map(|last| self.move_to_uci(last)),
+        }
+    }
+
     pub(crate) fn print(&self, highlight: Option<&Vec<Loc>>) {
         println!(
             "\n{}'s turn, {} half move",
@@ -141,88 +258,16 @@ impl Board {
         println!();
     }
 
-    pub(crate) const DEFAULT_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR";
-    pub(crate) fn load_fen(&mut self, fen: &str) {
-        for (y, row) in fen.split('/').enumerate() {
-            let mut x = 0;
-            for c in row.chars() {
-                if c.is_ascii_digit() {
-                    x += c.to_digit(10).unwrap() as usize;
-                } else {
-                    let color = if c.is_uppercase() {
-                        Color::WHITE
-                    } else {
-                        Color::BLACK
-                    };
-                    let piece = DefaultPiece::from_char(c.to_ascii_lowercase());
-                    if let Some(piece) = piece {
-                        self.insert(&Piece {
-                            loc: Loc(x, y),
-                            color,
-                            info_index: piece,
-                        });
-                        x += 1;
-                    }
-                }
-            }
-        }
-    }
-
-    pub(crate) fn to_fen(&self) -> String {
-        let mut fen = String::new();
-        for y in 0..self.height {
-            let mut empty = 0;
-            for x in 0..self.width {
-                let piece = self.get(&Loc(x, y));
-                if let Some(piece) = piece {
-                    if empty > 0 {
-                        fen.push_str(&empty.to_string());
-                        empty = 0;
-                    }
-                    let info = piece.info(self);
-                    fen.push(if piece.color == Color::WHITE {
-                        info.icon.to_ascii_uppercase()
-                    } else {
-                        info.icon
-                    });
-                } else {
-                    empty += 1;
-                }
-            }
-
-            if empty > 0 {
-                fen.push_str(&empty.to_string());
-            }
-
-            if y < self.height - 1 {
-                fen.push('/');
-            }
-        }
-        fen
-    }
-
-    pub(crate) fn load_cpgn(&mut self, _cpgn: &str) {
-        todo!()
-    }
-    pub(crate) fn to_cpgn(&self, metadata: CpgnMetadata) -> Cpgn {
-        Cpgn {
-            metadata,
-            moves: serde_json::to_string(&self.move_history).unwrap(),
-        }
-    }
-
+    /// Inserts a piece onto the board, marking it as not having moved yet
     pub(crate) fn insert(&mut self, piece: &Piece) {
         let index = self.loc_as_bit(&piece.loc);
         self.general_locations[piece.color].set(index, true);
+        self.first_moves[piece.color].set(index, true);
         self.piece_locations[piece.color]
             .get_mut(&piece.info_index)
             .unwrap()
             .set(index, true);
-    }
-
-    pub(crate) fn load_piece(&mut self, info: PieceInfo) -> usize {
-        let index = self.pieces.len() + 1;
-        self.pieces.insert(index, info);
-        index
+        self.position_hash ^= self.zobrist.piece(piece.color, piece.info_index, index);
+        self.refresh_attacks(&[index]);
     }
 }
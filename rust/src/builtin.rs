@@ -0,0 +1,482 @@
+use crate::attributes::castle::Castle;
+use crate::attributes::jumping::Jumping;
+use crate::attributes::main::PieceAttribute;
+use crate::attributes::promotion::Promotion;
+use crate::attributes::sliding::Sliding;
+use crate::board::{Board, PieceInfo, WinCondition};
+use crate::piece::{default_pieces, DefaultPiece, PieceType};
+use crate::util::ILoc;
+use crate::variant::{PromotionRule, Variant};
+
+/// The names of the variants that ship with the engine, see `Variant::builtin`
+pub(crate) const BUILTIN_VARIANTS: [&str; 7] = [
+    "Chess",
+    "Capablanca",
+    "Grand",
+    "Los Alamos",
+    "Gardner",
+    "Shatranj",
+    "Makruk",
+];
+
+fn orthogonal() -> Vec<ILoc> {
+    vec![ILoc(1, 0), ILoc(-1, 0), ILoc(0, 1), ILoc(0, -1)]
+}
+
+fn diagonal() -> Vec<ILoc> {
+    vec![ILoc(1, 1), ILoc(-1, 1), ILoc(1, -1), ILoc(-1, -1)]
+}
+
+fn knight() -> Vec<ILoc> {
+    vec![
+        ILoc(1, 2),
+        ILoc(2, 1),
+        ILoc(-1, 2),
+        ILoc(-2, 1),
+        ILoc(1, -2),
+        ILoc(2, -1),
+        ILoc(-1, -2),
+        ILoc(-2, -1),
+    ]
+}
+
+fn leaper(directions: Vec<ILoc>) -> PieceAttribute {
+    Jumping {
+        directions,
+        capture: true,
+        ..Default::default()
+    }
+    .into()
+}
+
+fn rider(directions: Vec<ILoc>) -> PieceAttribute {
+    Sliding {
+        directions,
+        capture: true,
+        ..Default::default()
+    }
+    .into()
+}
+
+fn piece(
+    id: PieceType,
+    display: &str,
+    icon: char,
+    value: i32,
+    attributes: Vec<PieceAttribute>,
+) -> PieceInfo {
+    PieceInfo {
+        id,
+        display: display.to_string(),
+        icon,
+        value,
+        image_path: [format!("b{icon}.png"), format!("w{icon}.png")],
+        attributes,
+        royal: false,
+    }
+}
+
+/// A standard piece, without the attributes `keep` returns false for
+fn standard(id: PieceType, keep: impl Fn(&PieceAttribute) -> bool) -> PieceInfo {
+    let mut info = default_pieces().remove(&id).unwrap();
+    info.attributes.retain(keep);
+    info
+}
+
+/// A pawn that only steps one square forward and captures diagonally, promoting as the variant says
+fn short_pawn() -> PieceInfo {
+    standard(
+        DefaultPiece::PAWN,
+        |attribute| matches!(attribute, PieceAttribute::Jumping(jumping) if !jumping.first_move_only),
+    )
+}
+
+/// A king that can't castle
+fn plain_king() -> PieceInfo {
+    standard(DefaultPiece::KING, |attribute| {
+        !matches!(attribute, PieceAttribute::Castle(_))
+    })
+}
+
+/// A piece that moves like a bishop and a knight
+fn archbishop(id: PieceType, display: &str, icon: char) -> PieceInfo {
+    piece(
+        id,
+        display,
+        icon,
+        7,
+        vec![rider(diagonal()), leaper(knight())],
+    )
+}
+
+/// A piece that moves like a rook and a knight
+fn chancellor(id: PieceType, display: &str, icon: char) -> PieceInfo {
+    piece(
+        id,
+        display,
+        icon,
+        8,
+        vec![rider(orthogonal()), leaper(knight())],
+    )
+}
+
+/// One step diagonally
+fn ferz(id: PieceType, display: &str, icon: char) -> PieceInfo {
+    piece(id, display, icon, 2, vec![leaper(diagonal())])
+}
+
+fn promotion(piece: PieceType, rows: Vec<usize>, pieces: Vec<PieceType>) -> PromotionRule {
+    PromotionRule {
+        piece,
+        promotion: Promotion {
+            rows,
+            black_rows: None,
+            pieces,
+            optional: false,
+        },
+    }
+}
+
+/// Standard chess on an 8x8 board
+fn chess() -> Variant {
+    Variant {
+        name: "Chess".to_string(),
+        width: 8,
+        height: 8,
+        default_pieces: true,
+        pieces: vec![],
+        fen: Board::DEFAULT_FEN.to_string(),
+        royal: None,
+        win_conditions: vec![WinCondition::Checkmate],
+        promotions: vec![],
+        move_rule: Some(50),
+        first_move_rows: vec![1],
+    }
+}
+
+/// Capablanca chess, on a 10x8 board with an archbishop and a chancellor, where the king castles three squares
+fn capablanca() -> Variant {
+    let (archbishop_id, chancellor_id) = (6, 7);
+    let mut king = plain_king();
+    king.attributes.push(
+        Castle {
+            destinations: vec![ILoc(3, 0), ILoc(-3, 0)],
+            rook: vec![ILoc(4, 0), ILoc(-5, 0)],
+            rook_destination: vec![ILoc(2, 0), ILoc(-2, 0)],
+            ..Default::default()
+        }
+        .into(),
+    );
+
+    Variant {
+        name: "Capablanca".to_string(),
+        width: 10,
+        height: 8,
+        pieces: vec![
+            king,
+            archbishop(archbishop_id, "Archbishop", 'a'),
+            chancellor(chancellor_id, "Chancellor", 'c'),
+        ],
+        fen: "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1".to_string(),
+        promotions: vec![promotion(
+            DefaultPiece::PAWN,
+            vec![0],
+            vec![
+                DefaultPiece::QUEEN,
+                chancellor_id,
+                archbishop_id,
+                DefaultPiece::ROOK,
+                DefaultPiece::BISHOP,
+                DefaultPiece::KNIGHT,
+            ],
+        )],
+        ..chess()
+    }
+}
+
+/// Grand chess, on a 10x10 board with a marshal and a cardinal, pawns starting on the third row and no castling
+///
+/// Pawns may promote on the eighth and ninth rows and must on the last one.
+/// Promoting to any piece is allowed, not only to pieces that have been captured
+fn grand() -> Variant {
+    let (marshal_id, cardinal_id) = (6, 7);
+    let pieces = vec![
+        DefaultPiece::QUEEN,
+        marshal_id,
+        cardinal_id,
+        DefaultPiece::ROOK,
+        DefaultPiece::BISHOP,
+        DefaultPiece::KNIGHT,
+    ];
+    let mut optional = promotion(DefaultPiece::PAWN, vec![1, 2], pieces.clone());
+    optional.promotion.optional = true;
+
+    Variant {
+        name: "Grand".to_string(),
+        width: 10,
+        height: 10,
+        pieces: vec![
+            plain_king(),
+            chancellor(marshal_id, "Marshal", 'm'),
+            archbishop(cardinal_id, "Cardinal", 'c'),
+        ],
+        fen: "r8r/1nbqkmcbn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKMCBN1/R8R w - - 0 1"
+            .to_string(),
+        promotions: vec![promotion(DefaultPiece::PAWN, vec![0], pieces), optional],
+        first_move_rows: vec![2],
+        ..chess()
+    }
+}
+
+/// Los Alamos chess, on a 6x6 board without bishops, castling, double steps or en passant
+fn los_alamos() -> Variant {
+    let keep = |_: &PieceAttribute| true;
+    Variant {
+        name: "Los Alamos".to_string(),
+        width: 6,
+        height: 6,
+        default_pieces: false,
+        pieces: vec![
+            short_pawn(),
+            standard(DefaultPiece::KNIGHT, keep),
+            standard(DefaultPiece::ROOK, keep),
+            standard(DefaultPiece::QUEEN, keep),
+            plain_king(),
+        ],
+        fen: "rnqknr/pppppp/6/6/PPPPPP/RNQKNR w - - 0 1".to_string(),
+        promotions: vec![promotion(
+            DefaultPiece::PAWN,
+            vec![0],
+            vec![
+                DefaultPiece::QUEEN,
+                DefaultPiece::ROOK,
+                DefaultPiece::KNIGHT,
+            ],
+        )],
+        ..chess()
+    }
+}
+
+/// Gardner's minichess, every standard piece on a 5x5 board without castling, double steps or en passant
+fn gardner() -> Variant {
+    let keep = |_: &PieceAttribute| true;
+    Variant {
+        name: "Gardner".to_string(),
+        width: 5,
+        height: 5,
+        default_pieces: false,
+        pieces: vec![
+            short_pawn(),
+            standard(DefaultPiece::KNIGHT, keep),
+            standard(DefaultPiece::BISHOP, keep),
+            standard(DefaultPiece::ROOK, keep),
+            standard(DefaultPiece::QUEEN, keep),
+            plain_king(),
+        ],
+        fen: "rnbqk/ppppp/5/PPPPP/RNBQK w - - 0 1".to_string(),
+        promotions: vec![promotion(
+            DefaultPiece::PAWN,
+            vec![0],
+            vec![
+                DefaultPiece::QUEEN,
+                DefaultPiece::ROOK,
+                DefaultPiece::BISHOP,
+                DefaultPiece::KNIGHT,
+            ],
+        )],
+        ..chess()
+    }
+}
+
+/// Shatranj, the old Persian game with the ferz and alfil instead of the queen and bishop
+///
+/// Stalemating the opponent or capturing all of their pieces but the king also wins
+fn shatranj() -> Variant {
+    let (ferz_id, alfil_id) = (6, 7);
+    let keep = |_: &PieceAttribute| true;
+    Variant {
+        name: "Shatranj".to_string(),
+        width: 8,
+        height: 8,
+        default_pieces: false,
+        pieces: vec![
+            short_pawn(),
+            standard(DefaultPiece::KNIGHT, keep),
+            standard(DefaultPiece::ROOK, keep),
+            plain_king(),
+            ferz(ferz_id, "Ferz", 'f'),
+            piece(
+                alfil_id,
+                "Alfil",
+                'e',
+                1,
+                vec![leaper(vec![
+                    ILoc(2, 2),
+                    ILoc(-2, 2),
+                    ILoc(2, -2),
+                    ILoc(-2, -2),
+                ])],
+            ),
+        ],
+        fen: "rnekfenr/pppppppp/8/8/8/8/PPPPPPPP/RNEFKENR w - - 0 1".to_string(),
+        win_conditions: vec![
+            WinCondition::Checkmate,
+            WinCondition::Stalemate,
+            WinCondition::BareKing,
+        ],
+        promotions: vec![promotion(DefaultPiece::PAWN, vec![0], vec![ferz_id])],
+        ..chess()
+    }
+}
+
+/// Makruk, Thai chess, with pawns starting on the third row and promoting to a met on the sixth
+fn makruk() -> Variant {
+    let (khon_id, met_id) = (6, 7);
+    let keep = |_: &PieceAttribute| true;
+    let mut khon = diagonal();
+    khon.push(ILoc(0, -1));
+
+    Variant {
+        name: "Makruk".to_string(),
+        width: 8,
+        height: 8,
+        default_pieces: false,
+        pieces: vec![
+            short_pawn(),
+            standard(DefaultPiece::KNIGHT, keep),
+            standard(DefaultPiece::ROOK, keep),
+            plain_king(),
+            piece(
+                khon_id,
+                "Khon",
+                's',
+                3,
+                vec![Jumping {
+                    black_directions: Some(
+                        khon.iter()
+                            .map(|offset| ILoc(offset.0, -offset.1))
+                            .collect(),
+                    ),
+                    directions: khon,
+                    capture: true,
+                    ..Default::default()
+                }
+                .into()],
+            ),
+            ferz(met_id, "Met", 'm'),
+        ],
+        fen: "rnsmksnr/8/pppppppp/8/8/PPPPPPPP/8/RNSKMSNR w - - 0 1".to_string(),
+        promotions: vec![promotion(DefaultPiece::PAWN, vec![2], vec![met_id])],
+        ..chess()
+    }
+}
+
+impl Variant {
+    /// A variant that ships with the engine, found by name ignoring case
+    pub(crate) fn builtin(name: &str) -> Option<Variant> {
+        let variant = match name.to_lowercase().as_str() {
+            "chess" => chess(),
+            "capablanca" => capablanca(),
+            "grand" => grand(),
+            "los alamos" => los_alamos(),
+            "gardner" => gardner(),
+            "shatranj" => shatranj(),
+            "makruk" => makruk(),
+            _ => return None,
+        };
+        Some(variant)
+    }
+}
+
+#[test]
+fn test_builtin_variants() {
+    use crate::board::GameStatus;
+    use crate::piece::Color;
+
+    for name in BUILTIN_VARIANTS {
+        let variant = Variant::builtin(name).unwrap();
+        assert_eq!(variant.name, name);
+        let board = Board::from_variant(&variant).unwrap();
+        assert_eq!(board.to_fen(), variant.fen);
+        assert_eq!(board.status(), GameStatus::Ongoing);
+
+        // Every variant can be shared as a file
+        let shared = Variant::parse(&variant.to_json()).unwrap();
+        assert_eq!(Board::from_variant(&shared).unwrap().to_fen(), variant.fen);
+    }
+    assert!(Variant::builtin("los alamos").is_some());
+    assert!(Variant::builtin("Xiangqi").is_none());
+
+    // Capablanca castles three squares towards either rook
+    let mut board = Board::from_variant(&Variant::builtin("Capablanca").unwrap()).unwrap();
+    board
+        .load_fen("r4k3r/10/10/10/10/10/10/R4K3R w KQkq - 0 1")
+        .unwrap();
+    for uci in ["f1i1", "f8c8"] {
+        board.move_piece(&board.parse_move(uci).unwrap()).unwrap();
+    }
+    assert_eq!(board.to_fen(), "2kr5r/10/10/10/10/10/10/R6RK1 w - - 2 2");
+
+    // Grand chess pawns may promote before the last row, but don't have to
+    let mut board = Board::from_variant(&Variant::builtin("Grand").unwrap()).unwrap();
+    board
+        .load_fen("4k5/10/P9/10/10/10/10/10/10/4K5 w - - 0 1")
+        .unwrap();
+    let a9 = board.notation_to_loc("a9").unwrap();
+    let pawn = board.get(&board.notation_to_loc("a8").unwrap()).unwrap();
+    assert_eq!(pawn.legal_moves(&board).len(), 7);
+    assert!(pawn
+        .legal_moves(&board)
+        .iter()
+        .all(|move_data| move_data.to == a9));
+
+    // Shatranj is won by taking everything but the king
+    let mut board = Board::from_variant(&Variant::builtin("Shatranj").unwrap()).unwrap();
+    board.load_fen("4k3/8/8/8/8/8/3r4/3RK3 w - - 0 1").unwrap();
+    board
+        .move_piece(&board.parse_move("d1d2").unwrap())
+        .unwrap();
+    assert_eq!(
+        board.status(),
+        GameStatus::Won(Color::WHITE, WinCondition::BareKing)
+    );
+
+    // Makruk pawns promote on the sixth row, to a met
+    let mut board = Board::from_variant(&Variant::builtin("Makruk").unwrap()).unwrap();
+    board.load_fen("4k3/8/8/P7/8/8/8/4K3 w - - 0 1").unwrap();
+    board
+        .move_piece(&board.parse_move("a5a6m").unwrap())
+        .unwrap();
+    assert_eq!(board.to_fen(), "4k3/8/M7/8/8/8/8/4K3 b - - 0 1");
+    // Changed pawns aren't standard chess, even on an 8x8 board
+    assert_eq!(
+        board.to_pgn(crate::cpgn::CpgnMetadata::default()),
+        Err(crate::pgn::PgnError::NotStandard)
+    );
+}
+
+#[test]
+fn test_builtin_perft() {
+    // Node counts from each starting position, chess from the perft results on the Chess Programming Wiki
+    // and the other variants from the counts published for them
+    let known: [(&str, &[u64]); 7] = [
+        ("Chess", &[20, 400, 8902]),
+        ("Capablanca", &[28, 784, 25228]),
+        ("Grand", &[65, 4225]),
+        ("Los Alamos", &[10, 100, 1212]),
+        ("Gardner", &[7, 53, 506]),
+        ("Shatranj", &[16, 256, 4176]),
+        ("Makruk", &[23, 529, 12012]),
+    ];
+    for (name, nodes) in known {
+        let mut board = Board::from_variant(&Variant::builtin(name).unwrap()).unwrap();
+        for (depth, expected) in nodes.iter().enumerate() {
+            assert_eq!(
+                board.perft(depth + 1),
+                *expected,
+                "{name} at depth {}",
+                depth + 1
+            );
+        }
+    }
+}
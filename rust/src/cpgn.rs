@@ -0,0 +1,379 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::{Board, PieceInfo};
+use crate::fen::{fen_half_moves, FenError};
+use crate::piece::{default_pieces, PieceType};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CpgnMetadata {
+    pub(crate) white: Option<String>,
+    pub(crate) black: Option<String>,
+    pub(crate) result: Option<String>,
+    pub(crate) reason: Option<String>,
+}
+
+/// A game in custom pgn, written as tag pairs followed by numbered moves
+///
+/// ```text
+/// [White "Alice"]
+/// [Black "Bob"]
+/// [Result "1-0"]
+/// [FEN "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"]
+/// [Piece "7 {...}"]
+///
+/// 1. e2e4 e7e5 2. g1f3 1-0
+/// ```
+///
+/// Moves are the squares moved from and to, see `Board::move_to_uci`.
+/// Custom pieces are embedded as json after the id the fen and moves refer to them by
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Cpgn {
+    pub(crate) metadata: CpgnMetadata,
+    /// The position the game started from
+    pub(crate) fen: String,
+    /// Pieces that aren't part of standard chess
+    pub(crate) pieces: Vec<(PieceType, PieceInfo)>,
+    pub(crate) moves: Vec<String>,
+    /// Half moves played before the game started, used for move numbers
+    pub(crate) start_half_moves: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpgnError {
+    /// A line starting with '[' isn't a valid tag pair
+    Tag(String),
+    Fen(FenError),
+    /// A piece tag isn't an id followed by a piece definition
+    Piece(String),
+    /// The first move that couldn't be played, counted in half moves from the start of the game
+    IllegalMove {
+        half_move: usize,
+        notation: String,
+    },
+}
+impl fmt::Display for CpgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpgnError::Tag(line) => write!(f, "Invalid tag '{line}'"),
+            CpgnError::Fen(error) => write!(f, "Invalid starting position: {error}"),
+            CpgnError::Piece(error) => write!(f, "Invalid piece: {error}"),
+            CpgnError::IllegalMove {
+                half_move,
+                notation,
+            } => write!(
+                f,
+                "Move {}{} '{notation}' is illegal",
+                half_move / 2 + 1,
+                if half_move % 2 == 0 { "." } else { "..." }
+            ),
+        }
+    }
+}
+impl From<FenError> for CpgnError {
+    fn from(error: FenError) -> Self {
+        CpgnError::Fen(error)
+    }
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Parses a tag pair like `[White "Alice"]` into its name and unescaped value
+pub(crate) fn parse_tag(line: &str) -> Option<(&str, String)> {
+    let (name, value) = line
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next()?),
+            '"' => return None,
+            c => unescaped.push(c),
+        }
+    }
+    Some((name, unescaped))
+}
+
+/// If two pieces have the same rules and look the same
+pub(crate) fn same_piece(a: &PieceInfo, b: &PieceInfo) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+pub(crate) fn is_result(token: &str) -> bool {
+    matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+/// Puts move numbers before white's moves, and the result at the end
+pub(crate) fn numbered_moves(
+    moves: &[String],
+    start_half_moves: usize,
+    metadata: &CpgnMetadata,
+) -> Vec<String> {
+    let mut text = vec![];
+    for (i, notation) in moves.iter().enumerate() {
+        let half_move = start_half_moves + i;
+        if half_move % 2 == 0 {
+            text.push(format!("{}.", half_move / 2 + 1));
+        } else if i == 0 {
+            text.push(format!("{}...", half_move / 2 + 1));
+        }
+        text.push(notation.clone());
+    }
+    text.push(metadata.result.clone().unwrap_or("*".to_string()));
+    text
+}
+
+impl Cpgn {
+    pub(crate) fn parse(cpgn: &str) -> Result<Self, CpgnError> {
+        let mut metadata = CpgnMetadata::default();
+        let mut fen = Board::DEFAULT_FEN.to_string();
+        let mut pieces = vec![];
+        let mut moves = vec![];
+
+        for line in cpgn.lines().map(str::trim) {
+            if !line.starts_with('[') {
+                // Move numbers may be written apart or attached, like "1. e2e4" or "1.e2e4"
+                moves.extend(
+                    line.split_whitespace()
+                        .filter(|token| !is_result(token))
+                        .map(|token| token.trim_start_matches(|c: char| c.is_ascii_digit()))
+                        .map(|token| token.trim_start_matches('.'))
+                        .filter(|token| !token.is_empty())
+                        .map(str::to_string),
+                );
+                continue;
+            }
+
+            let (name, value) = parse_tag(line).ok_or_else(|| CpgnError::Tag(line.to_string()))?;
+            match name {
+                "White" => metadata.white = Some(value),
+                "Black" => metadata.black = Some(value),
+                "Result" => metadata.result = Some(value).filter(|result| result != "*"),
+                "Reason" => metadata.reason = Some(value),
+                "FEN" => fen = value,
+                "Piece" => {
+                    let (id, info) = value
+                        .split_once(char::is_whitespace)
+                        .ok_or_else(|| CpgnError::Piece(value.clone()))?;
+                    let id = id
+                        .parse::<PieceType>()
+                        .map_err(|_| CpgnError::Piece(format!("'{id}' is not a piece id")))?;
+                    let info = serde_json::from_str::<PieceInfo>(info)
+                        .map_err(|error| CpgnError::Piece(error.to_string()))?;
+                    pieces.push((id, info));
+                }
+                // Unknown tags are allowed, so other tools can add their own
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            metadata,
+            start_half_moves: fen_half_moves(&fen)?,
+            fen,
+            pieces,
+            moves,
+        })
+    }
+}
+impl fmt::Display for Cpgn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tags = [
+            ("White", &self.metadata.white),
+            ("Black", &self.metadata.black),
+            ("Result", &self.metadata.result),
+            ("Reason", &self.metadata.reason),
+        ];
+        for (name, value) in tags {
+            if let Some(value) = value {
+                writeln!(f, "[{name} \"{}\"]", escape(value))?;
+            }
+        }
+        writeln!(f, "[FEN \"{}\"]", escape(&self.fen))?;
+        for (id, info) in self.pieces.iter() {
+            let info = serde_json::to_string(info).map_err(|_| fmt::Error)?;
+            writeln!(f, "[Piece \"{id} {}\"]", escape(&info))?;
+        }
+        writeln!(f)?;
+
+        let text = numbered_moves(&self.moves, self.start_half_moves, &self.metadata);
+        write!(f, "{}", text.join(" "))
+    }
+}
+
+impl Board {
+    /// Exports the game from its starting position, embedding any pieces that aren't the ones of standard chess
+    pub(crate) fn to_cpgn(&self, metadata: CpgnMetadata) -> Cpgn {
+        let mut start = self.clone();
+        while start.unmake_move().is_some() {}
+
+        let defaults = default_pieces();
+        let mut pieces = self
+            .pieces
+            .iter()
+            .filter(|(id, info)| {
+                defaults
+                    .get(id)
+                    .map_or(true, |default| !same_piece(default, info))
+            })
+            .map(|(id, info)| (*id, info.clone()))
+            .collect::<Vec<_>>();
+        pieces.sort_by_key(|(id, _)| *id);
+
+        let fen = start.to_fen();
+        let mut moves = Vec::with_capacity(self.move_history.len());
+        for move_data in self.move_history.iter() {
+            moves.push(start.move_to_uci(move_data));
+            start.move_piece(move_data).unwrap();
+        }
+
+        Cpgn {
+            metadata,
+            fen,
+            pieces,
+            moves,
+            start_half_moves: self.start_half_moves,
+        }
+    }
+
+    /// Loads a game written in custom pgn, replaying every move to make sure it is legal
+    ///
+    /// Pieces already loaded under an id the game uses for a different piece are moved to a new id.
+    /// The board is left untouched if anything goes wrong
+    pub(crate) fn load_cpgn(&mut self, cpgn: &str) -> Result<CpgnMetadata, CpgnError> {
+        let cpgn = Cpgn::parse(cpgn)?;
+        let mut board = self.clone();
+
+        for (id, info) in cpgn.pieces {
+            match board.pieces.get(&id) {
+                Some(existing) if same_piece(existing, &info) => {}
+                Some(_) => {
+                    board
+                        .remap_piece(id, board.next_piece_id)
+                        .map_err(|error| CpgnError::Piece(error.to_string()))?;
+                    board.load_piece_as(id, info);
+                }
+                None => board.load_piece_as(id, info),
+            }
+        }
+        board.load_fen(&cpgn.fen)?;
+
+        for (i, notation) in cpgn.moves.iter().enumerate() {
+            let half_move = board.start_half_moves + i;
+            let illegal = || CpgnError::IllegalMove {
+                half_move,
+                notation: notation.clone(),
+            };
+            let move_data = board.parse_move(notation).map_err(|_| illegal())?;
+            board.move_piece(&move_data).map_err(|_| illegal())?;
+        }
+
+        *self = board;
+        Ok(cpgn.metadata)
+    }
+}
+
+#[test]
+fn test_cpgn() {
+    use crate::attributes::jumping::Jumping;
+    use crate::attributes::main::PieceAttribute;
+    use crate::util::ILoc;
+
+    let mut board = Board::new(8, 8);
+    board.load_fen(Board::DEFAULT_FEN).unwrap();
+    for notation in ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1"] {
+        let move_data = board.parse_move(notation).unwrap();
+        board.move_piece(&move_data).unwrap();
+    }
+
+    let metadata = CpgnMetadata {
+        white: Some("Alice \"A\"".to_string()),
+        black: Some("Bob".to_string()),
+        ..Default::default()
+    };
+    let text = board.to_cpgn(metadata.clone()).to_string();
+    assert!(text.contains("[White \"Alice \\\"A\\\"\"]"));
+    assert!(text.ends_with("1. e2e4 e7e5 2. g1f3 b8c6 3. f1c4 g8f6 4. e1g1 *"));
+
+    let mut loaded = Board::new(8, 8);
+    assert_eq!(loaded.load_cpgn(&text), Ok(metadata));
+    assert_eq!(loaded.to_fen(), board.to_fen());
+    assert_eq!(loaded.move_history, board.move_history);
+
+    // The first illegal move is reported, and the board is left alone
+    let before = loaded.to_fen();
+    let error = loaded
+        .load_cpgn("1. e2e4 e7e5 2.g1f3 b8c6 3. e1g1 g8f6")
+        .unwrap_err();
+    assert_eq!(
+        error,
+        CpgnError::IllegalMove {
+            half_move: 4,
+            notation: "e1g1".to_string()
+        }
+    );
+    assert_eq!(error.to_string(), "Move 3. 'e1g1' is illegal");
+    assert_eq!(loaded.to_fen(), before);
+
+    // Custom pieces and starting positions survive a round trip
+    let mut board = Board::new(8, 8);
+    let camel = board.load_piece(PieceInfo {
+        id: 0,
+        display: "Camel".to_string(),
+        icon: 'c',
+        value: 3,
+        image_path: ["".to_string(), "".to_string()],
+        attributes: vec![PieceAttribute::Jumping(Jumping {
+            directions: vec![ILoc(3, 1), ILoc(1, 3), ILoc(-3, 1), ILoc(-1, 3)]
+                .into_iter()
+                .flat_map(|offset| [offset, ILoc(offset.0, -offset.1)])
+                .collect(),
+            capture: true,
+            ..Default::default()
+        })],
+        royal: false,
+    });
+    board.load_fen("4k3/8/8/8/8/8/8/C3K3 b - - 3 20").unwrap();
+    board
+        .move_piece(&board.parse_move("e8d8").unwrap())
+        .unwrap();
+    board
+        .move_piece(&board.parse_move("a1d2").unwrap())
+        .unwrap();
+
+    let text = board.to_cpgn(CpgnMetadata::default()).to_string();
+    assert!(text.contains(&format!("[Piece \"{camel} {{")));
+    assert!(text.ends_with("20... e8d8 21. a1d2 *"));
+
+    let mut loaded = Board::new(8, 8);
+    loaded.load_cpgn(&text).unwrap();
+    assert!(same_piece(&loaded.pieces[&camel], &board.pieces[&camel]));
+    assert_eq!(loaded.to_fen(), board.to_fen());
+
+    // A different piece already using the id is moved out of the way
+    let mut loaded = Board::new(8, 8);
+    let zebra = PieceInfo {
+        icon: 'z',
+        ..board.pieces[&camel].clone()
+    };
+    assert_eq!(loaded.load_piece(zebra), camel);
+    loaded.load_cpgn(&text).unwrap();
+    assert_eq!(loaded.pieces[&camel].icon, 'c');
+    assert_eq!(loaded.pieces[&(camel + 1)].icon, 'z');
+
+    assert!(matches!(
+        Board::new(8, 8).load_cpgn("[FEN \"8/8 x\"]"),
+        Err(CpgnError::Fen(_))
+    ));
+    assert!(matches!(
+        Board::new(8, 8).load_cpgn("[White Alice]"),
+        Err(CpgnError::Tag(_))
+    ));
+}
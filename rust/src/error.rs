@@ -0,0 +1,106 @@
+use std::{error, fmt};
+
+use wasm_bindgen::{JsError, JsValue};
+
+use crate::attributes::main::OptionType;
+use crate::board::MoveError;
+use crate::cpgn::CpgnError;
+use crate::fen::FenError;
+use crate::pgn::PgnError;
+use crate::piece::{ColorType, PieceType};
+use crate::util::NotationError;
+use crate::validation::PieceProblem;
+
+/// Everything that can go wrong with bad input, thrown as a js exception at the wasm boundary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinnyError {
+    InvalidFen(FenError),
+    /// No piece is registered under the given id
+    UnknownPieceType(PieceType),
+    /// Another piece is already registered under the given id
+    PieceIdTaken(PieceType),
+    /// An attribute option was given a value of the wrong type
+    BadOptionValue {
+        option: String,
+        expected: OptionType,
+    },
+    /// A move, as it was written, that isn't legal in the position
+    IllegalMove(String),
+    /// A move that can't be made, like one by the side that isn't to move
+    InvalidMove(MoveError),
+    InvalidCpgn(CpgnError),
+    InvalidPgn(PgnError),
+    /// A square that isn't valid notation, or isn't on the board
+    InvalidSquare(NotationError),
+    /// A color other than white (0) or black (1)
+    InvalidColor(ColorType),
+    /// A piece that can't be loaded, with everything wrong with it
+    InvalidPiece(Vec<PieceProblem>),
+    /// A variant file that isn't valid json or toml, or is missing something
+    InvalidVariant(String),
+    /// No built-in variant has the given name
+    UnknownVariant(String),
+}
+impl fmt::Display for LinnyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinnyError::InvalidFen(error) => write!(f, "Invalid fen: {error}"),
+            LinnyError::UnknownPieceType(id) => write!(f, "There is no piece with the id {id}"),
+            LinnyError::PieceIdTaken(id) => {
+                write!(f, "The id {id} is already used by another piece")
+            }
+            LinnyError::BadOptionValue { option, expected } => {
+                write!(f, "The option '{option}' should be a {expected:?}")
+            }
+            LinnyError::IllegalMove(notation) => write!(f, "'{notation}' is not a legal move"),
+            LinnyError::InvalidMove(error) => write!(f, "{error}"),
+            LinnyError::InvalidCpgn(error) => write!(f, "Invalid cpgn: {error}"),
+            LinnyError::InvalidPgn(error) => write!(f, "Invalid pgn: {error}"),
+            LinnyError::InvalidSquare(error) => write!(f, "{error}"),
+            LinnyError::InvalidColor(color) => {
+                write!(f, "{color} is not a color, use 0 for white or 1 for black")
+            }
+            LinnyError::InvalidPiece(problems) => {
+                let problems = problems
+                    .iter()
+                    .map(|problem| problem.to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "Invalid piece: {}", problems.join(" "))
+            }
+            LinnyError::InvalidVariant(error) => write!(f, "Invalid variant: {error}"),
+            LinnyError::UnknownVariant(name) => write!(f, "There is no variant called '{name}'"),
+        }
+    }
+}
+impl error::Error for LinnyError {}
+
+impl From<FenError> for LinnyError {
+    fn from(error: FenError) -> Self {
+        LinnyError::InvalidFen(error)
+    }
+}
+impl From<MoveError> for LinnyError {
+    fn from(error: MoveError) -> Self {
+        LinnyError::InvalidMove(error)
+    }
+}
+impl From<CpgnError> for LinnyError {
+    fn from(error: CpgnError) -> Self {
+        LinnyError::InvalidCpgn(error)
+    }
+}
+impl From<PgnError> for LinnyError {
+    fn from(error: PgnError) -> Self {
+        LinnyError::InvalidPgn(error)
+    }
+}
+impl From<NotationError> for LinnyError {
+    fn from(error: NotationError) -> Self {
+        LinnyError::InvalidSquare(error)
+    }
+}
+impl From<LinnyError> for JsValue {
+    fn from(error: LinnyError) -> Self {
+        JsError::from(error).into()
+    }
+}
@@ -0,0 +1,610 @@
+use std::fmt;
+
+use crate::attributes::jumping::Jumping;
+use crate::attributes::main::PieceAttribute;
+use crate::attributes::sliding::Sliding;
+use crate::board::Board;
+use crate::board_util::notation_to_loc;
+#[cfg(test)]
+use crate::piece::DefaultPiece;
+use crate::piece::{Color, ColorTrait, ColorType, Piece, PieceType};
+use crate::util::{ILoc, Loc};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// More than the six standard fields were given
+    TooManyFields(usize),
+    /// A row of the placement has no squares
+    EmptyRow(usize),
+    /// A row of the placement doesn't add up to the width of the first row
+    RowLength {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    UnknownPiece(char),
+    UnknownPieceId(String),
+    SideToMove(String),
+    Castling(char),
+    EnPassant(String),
+    Number {
+        field: &'static str,
+        value: String,
+    },
+}
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::TooManyFields(found) => {
+                write!(f, "Expected at most 6 fields, found {found}")
+            }
+            FenError::EmptyRow(row) => write!(f, "Row {} has no squares", row + 1),
+            FenError::RowLength {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Row {} should have {expected} squares, found {found}",
+                row + 1
+            ),
+            FenError::UnknownPiece(c) => write!(f, "Unknown piece '{c}'"),
+            FenError::UnknownPieceId(id) => write!(f, "Unknown piece id '{id}'"),
+            FenError::SideToMove(value) => {
+                write!(f, "Side to move should be 'w' or 'b', found '{value}'")
+            }
+            FenError::Castling(c) => write!(f, "Unknown castling right '{c}'"),
+            FenError::EnPassant(value) => {
+                write!(f, "'{value}' is not a valid en passant square")
+            }
+            FenError::Number { field, value } => {
+                write!(f, "The {field} should be a number, found '{value}'")
+            }
+        }
+    }
+}
+
+/// Castling rights are written as 'K' for castles towards the right, and 'Q' towards the left
+fn castle_side(rook: &ILoc) -> char {
+    if rook.0 > 0 {
+        'k'
+    } else {
+        'q'
+    }
+}
+
+fn castling_char(side: char, color: ColorType) -> char {
+    if color == Color::WHITE {
+        side.to_ascii_uppercase()
+    } else {
+        side
+    }
+}
+
+fn fen_turn(fields: &[&str]) -> Result<ColorType, FenError> {
+    match fields.get(1).copied().unwrap_or("w") {
+        "w" => Ok(Color::WHITE),
+        "b" => Ok(Color::BLACK),
+        value => Err(FenError::SideToMove(value.to_string())),
+    }
+}
+
+fn fen_number(
+    fields: &[&str],
+    index: usize,
+    field: &'static str,
+    default: usize,
+) -> Result<usize, FenError> {
+    fields.get(index).map_or(Ok(default), |value| {
+        value.parse::<usize>().map_err(|_| FenError::Number {
+            field,
+            value: value.to_string(),
+        })
+    })
+}
+
+/// Half moves played before a position with the given side to move and full move number, which counts from 1
+fn half_moves_before(turn: ColorType, full_moves: usize) -> usize {
+    (full_moves.max(1) - 1) * 2 + usize::from(turn == Color::BLACK)
+}
+
+/// Half moves played before the position of a fen, from its side to move and full move number
+pub(crate) fn fen_half_moves(fen: &str) -> Result<usize, FenError> {
+    let fields = fen.split_whitespace().collect::<Vec<_>>();
+    let full_moves = fen_number(&fields, 5, "full move number", 1)?;
+    Ok(half_moves_before(fen_turn(&fields)?, full_moves))
+}
+
+impl Board {
+    pub(crate) const DEFAULT_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    /// Loads all six fields of a fen, leaving the board untouched if it is invalid
+    ///
+    /// The board is resized to fit the placement. Fields after the placement may be left out, defaulting to `w - - 0 1`
+    pub(crate) fn load_fen(&mut self, fen: &str) -> Result<(), FenError> {
+        let fields = fen.split_whitespace().collect::<Vec<_>>();
+        if fields.len() > 6 {
+            return Err(FenError::TooManyFields(fields.len()));
+        }
+
+        let (width, height, pieces) =
+            self.parse_placement(fields.first().copied().unwrap_or(""))?;
+
+        let turn = fen_turn(&fields)?;
+
+        let castling = fields.get(2).copied().unwrap_or("-");
+        if let Some(c) = castling
+            .chars()
+            .find(|c| !matches!(c, 'K' | 'Q' | 'k' | 'q') && castling != "-")
+        {
+            return Err(FenError::Castling(c));
+        }
+
+        let en_passant = match fields.get(3).copied().unwrap_or("-") {
+            "-" => None,
+            value => {
+                let square = notation_to_loc(value, width, height)
+                    .map_err(|_| FenError::EnPassant(value.to_string()))?;
+                // The piece that passed over the square is one step past it in the direction the last side to move goes
+                let step = if turn == Color::WHITE {
+                    ILoc(0, 1)
+                } else {
+                    ILoc(0, -1)
+                };
+                let target = (square.as_iLoc() + step)
+                    .try_as_loc()
+                    .and_then(|loc| pieces.iter().find(|piece| piece.loc == loc))
+                    .filter(|piece| {
+                        piece.color == turn.other() && self.capturable_en_passant(piece.info_index)
+                    })
+                    .ok_or_else(|| FenError::EnPassant(value.to_string()))?;
+                Some((square, *target))
+            }
+        };
+
+        let half_move_clock = fen_number(&fields, 4, "half move clock", 0)?;
+        let full_moves = fen_number(&fields, 5, "full move number", 1)?;
+
+        // Every square is known to be valid from here on, so the board can be changed
+        if width != self.width || height != self.height {
+            self.resize(width, height);
+        }
+        self.clear();
+        for piece in pieces.iter() {
+            self.insert(piece);
+        }
+        self.apply_castling(castling);
+        self.apply_first_move_rows();
+
+        self.turn = turn;
+        self.half_move_clock = half_move_clock;
+        self.start_half_moves = half_moves_before(turn, full_moves);
+        self.start_en_passant = en_passant;
+
+        self.hashes.clear();
+        self.record_position();
+        Ok(())
+    }
+
+    /// Finds the piece a fen character stands for, uppercase being white
+    fn piece_from_icon(&self, c: char) -> Option<(PieceType, ColorType)> {
+        let color = if c.is_uppercase() {
+            Color::WHITE
+        } else {
+            Color::BLACK
+        };
+        let mut matches = self
+            .pieces
+            .iter()
+            .filter(|(_, info)| info.icon.to_ascii_lowercase() == c.to_ascii_lowercase());
+        match (matches.next(), matches.next()) {
+            (Some((piece_type, _)), None) => Some((*piece_type, color)),
+            _ => None,
+        }
+    }
+
+    /// How a piece is written in a fen, by icon if it is a letter no other piece uses, otherwise by id
+    fn piece_to_fen(&self, piece: &Piece) -> String {
+        let icon = piece.info(self).icon;
+        if icon.is_ascii_alphabetic() && self.piece_from_icon(icon).is_some() {
+            let icon = if piece.color == Color::WHITE {
+                icon.to_ascii_uppercase()
+            } else {
+                icon.to_ascii_lowercase()
+            };
+            icon.to_string()
+        } else if piece.color == Color::WHITE {
+            format!("[{}]", piece.info_index)
+        } else {
+            format!("{{{}}}", piece.info_index)
+        }
+    }
+
+    /// Parses the placement field, returning the width and height it describes along with its pieces
+    ///
+    /// Empty squares may take multiple digits, and pieces may be written by id as `[id]` for white or `{id}` for black
+    fn parse_placement(&self, placement: &str) -> Result<(usize, usize, Vec<Piece>), FenError> {
+        let rows = placement.split('/').collect::<Vec<_>>();
+        let mut width = None;
+        let mut pieces = vec![];
+
+        for (y, row) in rows.iter().enumerate() {
+            let mut x = 0;
+            let mut chars = row.chars().peekable();
+            while let Some(c) = chars.next() {
+                if c.is_ascii_digit() {
+                    let mut empty = c.to_digit(10).unwrap() as usize;
+                    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                        empty = empty * 10 + digit as usize;
+                        chars.next();
+                    }
+                    x += empty;
+                    continue;
+                }
+
+                let (info_index, color) = if c == '[' || c == '{' {
+                    let close = if c == '[' { ']' } else { '}' };
+                    let id = chars
+                        .by_ref()
+                        .take_while(|c| *c != close)
+                        .collect::<String>();
+                    let info_index = id
+                        .parse::<PieceType>()
+                        .ok()
+                        .filter(|id| self.pieces.contains_key(id))
+                        .ok_or(FenError::UnknownPieceId(id))?;
+                    let color = if c == '[' { Color::WHITE } else { Color::BLACK };
+                    (info_index, color)
+                } else {
+                    self.piece_from_icon(c).ok_or(FenError::UnknownPiece(c))?
+                };
+
+                pieces.push(Piece {
+                    loc: Loc(x, y),
+                    color,
+                    info_index,
+                });
+                x += 1;
+            }
+
+            match width {
+                None if x == 0 => return Err(FenError::EmptyRow(y)),
+                None => width = Some(x),
+                Some(width) if x != width => {
+                    return Err(FenError::RowLength {
+                        row: y,
+                        expected: width,
+                        found: x,
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        Ok((width.unwrap_or(0), rows.len(), pieces))
+    }
+
+    /// Marks castling pieces and their rooks as moved, unless the castling field gives them the right to castle
+    fn apply_castling(&mut self, castling: &str) {
+        for color in [Color::WHITE, Color::BLACK] {
+            for piece in self.pieces_of(color) {
+                let mut can_castle = false;
+                for (rook, side) in self.castle_rooks(&piece) {
+                    if castling.contains(castling_char(side, color)) {
+                        can_castle = true;
+                    } else {
+                        let index = self.loc_as_bit(&rook.loc);
+                        self.unset_first_move(color, index);
+                    }
+                }
+
+                if !can_castle && self.has_castle(&piece) {
+                    let index = self.loc_as_bit(&piece.loc);
+                    self.unset_first_move(color, index);
+                }
+            }
+        }
+    }
+
+    /// Marks pieces with first move only moves as moved if they aren't on one of `first_move_rows`, like a pawn that has advanced
+    fn apply_first_move_rows(&mut self) {
+        for color in [Color::WHITE, Color::BLACK] {
+            for piece in self.pieces_of(color) {
+                let row = if color == Color::WHITE {
+                    self.height - 1 - piece.loc.1
+                } else {
+                    piece.loc.1
+                };
+                let first_move_only = piece.info(self).attributes.iter().any(|attribute| {
+                    matches!(
+                        attribute,
+                        PieceAttribute::Jumping(Jumping {
+                            first_move_only: true,
+                            ..
+                        }) | PieceAttribute::Sliding(Sliding {
+                            first_move_only: true,
+                            ..
+                        })
+                    )
+                });
+
+                if first_move_only
+                    && !self.has_castle(&piece)
+                    && !self.first_move_rows.contains(&row)
+                {
+                    let index = self.loc_as_bit(&piece.loc);
+                    self.unset_first_move(color, index);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn has_castle(&self, piece: &Piece) -> bool {
+        piece
+            .info(self)
+            .attributes
+            .iter()
+            .any(|attribute| matches!(attribute, PieceAttribute::Castle(_)))
+    }
+
+    /// The rooks a piece could castle with, and which side they are on
+    pub(crate) fn castle_rooks(&self, piece: &Piece) -> Vec<(Piece, char)> {
+        let mut rooks = vec![];
+        for attribute in piece.info(self).attributes.iter() {
+            if let PieceAttribute::Castle(castle) = attribute {
+                let offsets = match &castle.black_rook {
+                    Some(black_rook) if piece.color == Color::BLACK => black_rook,
+                    _ => &castle.rook,
+                };
+                for offset in offsets.iter() {
+                    if let Some(loc) = (piece.loc.as_iLoc() + *offset).try_as_loc()
+                        && let Some(rook) = self.get(&loc)
+                        && rook.color == piece.color
+                        && rook.info_index == castle.rook_piece
+                    {
+                        rooks.push((rook, castle_side(offset)));
+                    }
+                }
+            }
+        }
+        rooks
+    }
+
+    fn castling_rights(&self) -> String {
+        let mut rights = String::new();
+        for color in [Color::WHITE, Color::BLACK] {
+            let mut sides = vec![];
+            for piece in self.pieces_of(color) {
+                if !self.is_first_move(&piece) {
+                    continue;
+                }
+                for (rook, side) in self.castle_rooks(&piece) {
+                    if self.is_first_move(&rook) && !sides.contains(&side) {
+                        sides.push(side);
+                    }
+                }
+            }
+            sides.sort();
+            rights.extend(sides.iter().map(|side| castling_char(*side, color)));
+        }
+
+        if rights.is_empty() {
+            rights.push('-');
+        }
+        rights
+    }
+
+    /// The square the en passant target passed over
+    pub(crate) fn en_passant_square(&self) -> Option<Loc> {
+        let target = self.en_passant_target()?;
+        if let Some(last) = self.move_history.last() {
+            let step = ILoc(
+                (last.piece.loc.0 as i16 - target.loc.0 as i16).signum(),
+                (last.piece.loc.1 as i16 - target.loc.1 as i16).signum(),
+            );
+            (target.loc.as_iLoc() + step).try_as_loc()
+        } else {
+            self.start_en_passant.map(|(square, _)| square)
+        }
+    }
+
+    /// Writes all six fields of a fen
+    pub(crate) fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for y in 0..self.height {
+            let mut empty = 0;
+            for x in 0..self.width {
+                let piece = self.get(&Loc(x, y));
+                if let Some(piece) = piece {
+                    if empty > 0 {
+                        fen.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    fen.push_str(&self.piece_to_fen(&piece));
+                } else {
+                    empty += 1;
+                }
+            }
+
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+
+            if y < self.height - 1 {
+                fen.push('/');
+            }
+        }
+
+        let turn = if self.turn == Color::WHITE { "w" } else { "b" };
+        let en_passant = self
+            .en_passant_square()
+            .map_or("-".to_string(), |loc| self.loc_to_notation(&loc));
+        format!(
+            "{fen} {turn} {} {en_passant} {} {}",
+            self.castling_rights(),
+            self.half_move_clock,
+            self.full_moves() + 1
+        )
+    }
+}
+
+#[test]
+fn test_fen() {
+    let fens = [
+        Board::DEFAULT_FEN,
+        "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 12 40",
+        "4k3/8/8/8/8/8/8/4K3 w - - 99 120",
+    ];
+    for fen in fens {
+        let mut board = Board::new(8, 8);
+        board.load_fen(fen).unwrap();
+        assert_eq!(board.to_fen(), fen);
+    }
+
+    // Castling rights and en passant are restored
+    let mut board = Board::new(8, 8);
+    board
+        .load_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1")
+        .unwrap();
+    let castles = board
+        .legal_moves(Color::WHITE)
+        .into_iter()
+        .filter(|move_data| move_data.castle.is_some())
+        .count();
+    assert_eq!(castles, 1);
+
+    let mut board = Board::new(8, 8);
+    board.load_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+    let pawn = board.get(&Loc(4, 3)).unwrap();
+    assert!(pawn
+        .legal_moves(&board)
+        .iter()
+        .any(|move_data| move_data.capture == Some(Loc(3, 3))));
+
+    // The square must be just behind a piece of the last side to move that can be taken en passant
+    for fen in [
+        "4k3/8/8/3pP3/8/8/8/4K3 w - e6 0 2",
+        "4k3/8/8/3pP3/8/8/8/4K3 w - d3 0 2",
+        "4k3/8/8/3nP3/8/8/8/4K3 w - d6 0 2",
+    ] {
+        let value = fen.split_whitespace().nth(3).unwrap().to_string();
+        assert_eq!(
+            Board::new(8, 8).load_fen(fen),
+            Err(FenError::EnPassant(value))
+        );
+    }
+
+    // Pawns that have left their starting row can't double step again
+    let mut board = Board::new(8, 8);
+    board.load_fen("4k3/8/8/8/4P3/8/3P4/4K3 w - - 0 1").unwrap();
+    assert!(!board.is_first_move(&board.get(&Loc(4, 4)).unwrap()));
+    assert!(board.is_first_move(&board.get(&Loc(3, 6)).unwrap()));
+    assert_eq!(board.legal_moves(Color::WHITE).len(), 1 + 2 + 4);
+    board.first_move_rows = vec![1, 3];
+    board.load_fen("4k3/8/8/8/4P3/8/3P4/4K3 w - - 0 1").unwrap();
+    assert_eq!(board.legal_moves(Color::WHITE).len(), 2 + 2 + 4);
+
+    // Moves update every field
+    let mut board = Board::new(8, 8);
+    board.load_fen(Board::DEFAULT_FEN).unwrap();
+    let pawn = board.get(&Loc(4, 6)).unwrap();
+    let double_step = pawn
+        .legal_moves(&board)
+        .into_iter()
+        .find(|move_data| move_data.to == Loc(4, 4))
+        .unwrap();
+    board.move_piece(&double_step).unwrap();
+    assert_eq!(
+        board.to_fen(),
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+    );
+
+    let errors = [
+        (
+            "rnbqkbnr/pppppp/8/8/8/8/PPPPPPPP/RNBQKBNR",
+            FenError::RowLength {
+                row: 1,
+                expected: 8,
+                found: 6,
+            },
+        ),
+        (
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR",
+            FenError::RowLength {
+                row: 2,
+                expected: 8,
+                found: 9,
+            },
+        ),
+        (
+            "rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR",
+            FenError::UnknownPiece('x'),
+        ),
+        ("8/8/8/8/8/8/8/8 x", FenError::SideToMove("x".to_string())),
+        ("8/8/8/8/8/8/8/8 w KX", FenError::Castling('X')),
+        (
+            "8/8/8/8/8/8/8/8 w - z9",
+            FenError::EnPassant("z9".to_string()),
+        ),
+        (
+            "8/8/8/8/8/8/8/8 w - - x",
+            FenError::Number {
+                field: "half move clock",
+                value: "x".to_string(),
+            },
+        ),
+        (
+            "8/8/8/8/8/8/8/[99]7",
+            FenError::UnknownPieceId("99".to_string()),
+        ),
+        ("/8", FenError::EmptyRow(0)),
+    ];
+    for (fen, error) in errors {
+        let mut board = Board::new(8, 8);
+        assert_eq!(board.load_fen(fen), Err(error));
+    }
+}
+
+#[test]
+fn test_extended_fen() {
+    // Boards wider than 9 and custom pieces by icon or id
+    let mut board = Board::new(8, 8);
+    let mut archbishop = board.pieces[&DefaultPiece::KNIGHT].clone();
+    archbishop.icon = 'a';
+    let archbishop = board.load_piece(archbishop);
+    let mut knightrider = board.pieces[&DefaultPiece::KNIGHT].clone();
+    knightrider.icon = '♞';
+    let knightrider = board.load_piece(knightrider);
+
+    let fen = format!("rnabqkbnr{{{knightrider}}}/10/10/10/10/10/10/[{knightrider}]8A w - - 0 1");
+    board.load_fen(&fen).unwrap();
+    assert_eq!((board.width, board.height), (10, 8));
+    assert_eq!(board.get(&Loc(2, 0)).unwrap().info_index, archbishop);
+    assert_eq!(board.get(&Loc(9, 0)).unwrap().info_index, knightrider);
+    assert_eq!(board.get(&Loc(0, 7)).unwrap().info_index, knightrider);
+    assert_eq!(board.get(&Loc(9, 7)).unwrap().color, Color::WHITE);
+    assert_eq!(board.to_fen(), fen);
+
+    let mut board = Board::new(8, 8);
+    board
+        .load_fen("4k5/10/10/10/10/10/10/10/10/4K5 w - - 0 1")
+        .unwrap();
+    assert_eq!((board.width, board.height), (10, 10));
+    assert_eq!(board.legal_moves(Color::WHITE).len(), 5);
+
+    // Files past z and ranks past 9
+    let fen = "4k23/28/28/26Pp/28/28/28/28/28/28/28/4K23 w - ab10 0 1";
+    board.load_fen(fen).unwrap();
+    assert_eq!(board.to_fen(), fen);
+    let en_passant = board.parse_move("aa9ab10").unwrap();
+    assert_eq!(board.move_to_san(&en_passant), "aaxab10");
+    assert_eq!(board.notation_to_loc("ab12"), Ok(Loc(27, 0)));
+    assert!(board.notation_to_loc("ac1").is_err());
+    assert!(board.notation_to_loc("a13").is_err());
+    assert_eq!(
+        board.load_fen("4k23/28/28/26Pp/28/28/28/28/28/28/28/4K23 w - ab13 0 1"),
+        Err(FenError::EnPassant("ab13".to_string()))
+    );
+}
@@ -5,15 +5,29 @@ use bit_vec::BitVec;
 use wasm_bindgen::prelude::wasm_bindgen;
 
 use crate::attributes::main::{MoveData, PieceAttribute, PieceAttributeTrait};
-use crate::board::Board;
+use crate::board::{Board, PieceInfo};
+use crate::builtin::BUILTIN_VARIANTS;
+use crate::error::LinnyError;
 use crate::piece::{Color, Piece};
 use crate::util::Loc;
+use crate::validation::PieceProblem;
+use crate::variant::Variant;
 
 mod attributes;
 mod board;
 mod board_util;
+mod builtin;
+mod cpgn;
+mod error;
+mod fen;
+mod notation;
+mod pgn;
 mod piece;
+mod registry;
 mod util;
+mod validation;
+mod variant;
+mod zobrist;
 
 fn bit_vec_to_list(map: &BitVec, board: &Board) -> Vec<Loc> {
     let mut vec = Vec::new();
@@ -29,18 +43,21 @@ fn main() {
     std::env::set_var("RUST_BACKTRACE", "1");
 
     let mut board = Board::new(8, 8);
-    board.load_fen(Board::DEFAULT_FEN);
-
-    board.raw_move(&MoveData {
-        castle: None,
-        piece: Piece {
-            color: Color::WHITE,
-            loc: Loc(6, 6),
-            info_index: 0,
-        },
-        to: Loc(6, 4),
-        capture: Some(Loc(6, 1)),
-    });
+    board.load_fen(Board::DEFAULT_FEN).unwrap();
+
+    board
+        .raw_move(&MoveData {
+            castle: None,
+            promotion: None,
+            piece: Piece {
+                color: Color::WHITE,
+                loc: Loc(6, 6),
+                info_index: 0,
+            },
+            to: Loc(6, 4),
+            capture: Some(Loc(6, 1)),
+        })
+        .unwrap();
 
     let test_piece = board.get(&Loc(6, 1));
     if let Some(piece) = test_piece {
@@ -51,48 +68,180 @@ fn main() {
     println!("{}", board.hash());
 }
 
-static mut GAME: Option<Board> = None;
+#[wasm_bindgen]
+/// A game the frontend holds on to, so it can have several at once, like the live game and an analysis board.
+pub struct Game {
+    board: Board,
+}
 
 #[wasm_bindgen]
-/// Sets the global game variable to a new board with the given fen, or the default fen if None is given.
-pub fn set_game(fen: Option<String>) {
-    unsafe {
-        GAME = Some(Board::new(8, 8));
-        let game = GAME.as_mut().unwrap();
-        if let Some(fen) = fen {
-            game.load_fen(&fen);
-        } else {
-            game.load_fen(Board::DEFAULT_FEN);
+impl Game {
+    #[wasm_bindgen(constructor)]
+    /// Creates a game with the given fen, or the default fen if None is given.
+    /// Throws a description of the problem if the fen is invalid.
+    pub fn new(fen: Option<String>) -> Result<Game, LinnyError> {
+        let mut board = Board::new(8, 8);
+        board.load_fen(fen.as_deref().unwrap_or(Board::DEFAULT_FEN))?;
+        Ok(Game { board })
+    }
+
+    /// Creates a game of a variant in its starting position, from the variant file as json or toml.
+    /// Throws a description of the problem if the variant is invalid.
+    pub fn from_variant(variant: String) -> Result<Game, LinnyError> {
+        let variant = Variant::parse(&variant)?;
+        Ok(Game {
+            board: Board::from_variant(&variant)?,
+        })
+    }
+
+    /// Creates a game of a built-in variant in its starting position, like "Capablanca", see `get_variant_names`.
+    /// Throws if there is no variant with that name.
+    pub fn from_variant_name(name: String) -> Result<Game, LinnyError> {
+        let variant = Variant::builtin(&name).ok_or(LinnyError::UnknownVariant(name))?;
+        Ok(Game {
+            board: Board::from_variant(&variant)?,
+        })
+    }
+
+    /// Copies the game along with its history, like for an analysis board.
+    pub fn copy(&self) -> Game {
+        Game {
+            board: self.board.clone(),
         }
     }
-}
 
-#[wasm_bindgen]
-/// Checks if a game has been set.
-pub fn game_exists() -> bool {
-    unsafe { GAME.is_some() }
+    /// Returns the current fen of the game.
+    pub fn get_fen(&self) -> String {
+        self.board.to_fen()
+    }
+
+    /// Returns the status of the game for the side to move as json.
+    pub fn get_status(&self) -> String {
+        serde_json::to_string(&self.board.status()).unwrap()
+    }
+
+    /// Returns the result of the game, like "1-0", or None if the game is still going.
+    pub fn get_result(&self) -> Option<String> {
+        self.board
+            .status()
+            .result()
+            .map(|result| result.to_string())
+    }
+
+    /// Returns the legal moves of the side to move as a json list of uci-style strings like "e2e4" or "e7e8q".
+    /// If a square like "e2" is given, only the moves of the piece on it are listed.
+    /// Throws a description of the problem if the square isn't on the board.
+    pub fn get_moves(&self, square: Option<String>) -> Result<String, LinnyError> {
+        let from = square
+            .map(|square| self.board.notation_to_loc(&square))
+            .transpose()?;
+        let moves = self
+            .board
+            .legal_moves(self.board.turn)
+            .iter()
+            .filter(|move_data| from.map_or(true, |from| move_data.piece.loc == from))
+            .map(|move_data| self.board.move_to_uci(move_data))
+            .collect::<Vec<_>>();
+        Ok(serde_json::to_string(&moves).unwrap())
+    }
+
+    /// Returns the board as json, with its size, the side to move, the status, the last move,
+    /// and every piece along with its square and info.
+    pub fn get_board(&self) -> String {
+        serde_json::to_string(&self.board.view()).unwrap()
+    }
+
+    /// Returns the squares the given color (0 for white, 1 for black) attacks as a json list like `["e4", "d5"]`.
+    /// Throws if the color isn't 0 or 1.
+    pub fn get_attacks(&self, color: usize) -> Result<String, LinnyError> {
+        let attacks = &self.board.attacks[Color::checked(color)?];
+        let squares = bit_vec_to_list(attacks, &self.board)
+            .iter()
+            .map(|loc| self.board.loc_to_notation(loc))
+            .collect::<Vec<_>>();
+        Ok(serde_json::to_string(&squares).unwrap())
+    }
+
+    /// Plays a move written like "e2e4", "e7e8q" or "e1g1".
+    /// Throws a description of the problem if the move isn't legal, or moves a piece of the side that isn't to move.
+    pub fn make_move(&mut self, uci: String) -> Result<(), LinnyError> {
+        let move_data = self.board.parse_move(&uci)?;
+        Ok(self.board.move_piece(&move_data)?)
+    }
+
+    /// Takes back the last move, returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        self.board.unmake_move().is_some()
+    }
+
+    /// Returns the info of the piece with the given id as json.
+    /// Throws if no piece has that id.
+    pub fn get_piece_info(&self, id: usize) -> Result<String, LinnyError> {
+        Ok(serde_json::to_string(self.board.piece_info(id)?).unwrap())
+    }
+
+    /// Checks a piece from its json info, returning a json list of problems for the piece editor,
+    /// each with the index of the attribute and the name of the option it is about, if any.
+    /// Pass the id of the piece being edited as `replacing` so it isn't compared with itself.
+    pub fn validate_piece(&self, piece: String, replacing: Option<usize>) -> String {
+        let problems = match serde_json::from_str::<PieceInfo>(&piece) {
+            Ok(piece) => self.board.validate_piece(&piece, replacing),
+            Err(err) => vec![PieceProblem::json(err)],
+        };
+        serde_json::to_string(&problems).unwrap()
+    }
+
+    /// Registers a piece from its json info, returning its id.
+    /// The id in the info is kept if no other piece has it, so saved pieces keep theirs.
+    /// Throws every problem found by `validate_piece` if the piece is invalid.
+    pub fn add_piece(&mut self, piece: String) -> Result<usize, LinnyError> {
+        let piece = self.checked_piece(&piece, None)?;
+        Ok(self.board.load_piece(piece))
+    }
+
+    /// Changes the rules of the piece with the given id, leaving every piece of its type where it is.
+    /// Moves it was part of can't be undone afterwards.
+    /// Throws if no piece has that id, or every problem found by `validate_piece` if the piece is invalid.
+    pub fn replace_piece(&mut self, id: usize, piece: String) -> Result<(), LinnyError> {
+        self.board.piece_info(id)?;
+        let piece = self.checked_piece(&piece, Some(id))?;
+        self.board.replace_piece(id, piece)
+    }
+
+    /// Removes the piece with the given id and every piece of its type from the board.
+    /// Moves it was part of can't be undone afterwards. Throws if no piece has that id.
+    pub fn remove_piece(&mut self, id: usize) -> Result<(), LinnyError> {
+        self.board.remove_piece(id).map(|_| ())
+    }
+}
+impl Game {
+    fn checked_piece(
+        &self,
+        piece: &str,
+        replacing: Option<usize>,
+    ) -> Result<PieceInfo, LinnyError> {
+        let piece = serde_json::from_str::<PieceInfo>(piece)
+            .map_err(|err| LinnyError::InvalidPiece(vec![PieceProblem::json(err)]))?;
+        let problems = self.board.validate_piece(&piece, replacing);
+        if !problems.is_empty() {
+            return Err(LinnyError::InvalidPiece(problems));
+        }
+        Ok(piece)
+    }
 }
 
+/// Returns the names of the built-in variants as a json list.
 #[wasm_bindgen]
-/// Returns the current fen of the game.
-pub fn get_fen() -> Option<String> {
-    unsafe { GAME.as_ref().map(|game| game.to_fen()) }
+pub fn get_variant_names() -> String {
+    serde_json::to_string(&BUILTIN_VARIANTS).unwrap()
 }
 
+/// Returns a built-in variant as a json variant file, to share or to start a custom variant from.
+/// Throws if there is no variant with that name.
 #[wasm_bindgen]
-pub fn add_piece(piece: String) -> Option<usize> {
-    unsafe {
-        if let Some(game) = &mut GAME {
-            let parsed = serde_json::from_str(&piece);
-            if let Ok(piece) = parsed {
-                Some(game.load_piece(piece))
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    }
+pub fn get_variant(name: String) -> Result<String, LinnyError> {
+    let variant = Variant::builtin(&name).ok_or(LinnyError::UnknownVariant(name))?;
+    Ok(variant.to_json())
 }
 
 #[wasm_bindgen]
@@ -106,3 +255,121 @@ pub fn get_attribute_infos() -> String {
 
     serde_json::to_string(&vec).unwrap()
 }
+
+#[test]
+fn test_games() {
+    use crate::board::MoveError;
+    use crate::piece::DefaultPiece;
+
+    let mut live = Game::new(None).unwrap();
+    live.make_move("e2e4".to_string()).unwrap();
+    let mut analysis = live.copy();
+    analysis.make_move("e7e5".to_string()).unwrap();
+
+    assert_eq!(
+        live.get_fen(),
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+    );
+    assert!(analysis.get_fen().contains(" w KQkq e6 0 2"));
+    assert!(live.make_move("e2e4".to_string()).is_err());
+    assert!(analysis.undo());
+    assert_eq!(analysis.get_fen(), live.get_fen());
+    assert!(Game::new(Some("8/8 x".to_string())).is_err());
+    let mini = Game::from_variant(
+        "name = \"Mini\"\nwidth = 5\nheight = 5\nfen = \"4k/5/5/5/K4 w - - 0 1\"".to_string(),
+    )
+    .unwrap();
+    assert_eq!(mini.get_fen(), "4k/5/5/5/K4 w - - 0 1");
+    assert!(matches!(
+        Game::from_variant("{}".to_string()),
+        Err(LinnyError::InvalidVariant(_))
+    ));
+    let makruk = Game::from_variant_name("makruk".to_string()).unwrap();
+    assert!(makruk.get_fen().starts_with("rnsmksnr/8/pppppppp/"));
+    assert_eq!(
+        Game::from_variant(get_variant("Makruk".to_string()).unwrap())
+            .unwrap()
+            .get_fen(),
+        makruk.get_fen()
+    );
+    assert!(get_variant_names().contains("\"Los Alamos\""));
+    assert_eq!(
+        get_variant("Xiangqi".to_string()),
+        Err(LinnyError::UnknownVariant("Xiangqi".to_string()))
+    );
+
+    assert_eq!(
+        live.get_moves(Some("g8".to_string())),
+        Ok(r#"["g8h6","g8f6"]"#.to_string())
+    );
+    assert_eq!(live.get_moves(Some("e2".to_string())), Ok("[]".to_string()));
+    assert!(live.get_moves(Some("e9".to_string())).is_err());
+    assert_eq!(
+        serde_json::from_str::<Vec<String>>(&live.get_moves(None).unwrap())
+            .unwrap()
+            .len(),
+        20
+    );
+
+    let attacks = live.get_attacks(Color::WHITE).unwrap();
+    assert!(attacks.contains(r#""d5""#) && attacks.contains(r#""f3""#));
+    assert!(!attacks.contains(r#""e5""#));
+    assert_eq!(live.get_attacks(2), Err(LinnyError::InvalidColor(2)));
+    assert_eq!(
+        live.make_move("e7e4".to_string()),
+        Err(LinnyError::IllegalMove("e7e4".to_string()))
+    );
+    assert_eq!(
+        live.make_move("d2d4".to_string()),
+        Err(LinnyError::InvalidMove(MoveError::WrongTurn))
+    );
+    assert!(live
+        .get_piece_info(DefaultPiece::KING)
+        .unwrap()
+        .contains("King"));
+    assert_eq!(
+        live.get_piece_info(99),
+        Err(LinnyError::UnknownPieceType(99))
+    );
+
+    let camel = r#"{"id": 0, "display": "Camel", "icon": "c", "value": 3, "image_path": ["", ""],
+        "attributes": [{"Jumping": {"directions": [[1, 3], [-1, 3], [1, -3], [-1, -3]],
+        "black_directions": null, "capture": true, "capture_only": false, "first_move_only": false}}]}"#;
+    let camel_json = camel.to_string();
+    assert_eq!(live.validate_piece(camel.to_string(), None), "[]");
+    let camel = live.add_piece(camel.to_string()).unwrap();
+    assert!(live.get_piece_info(camel).unwrap().contains("Camel"));
+    assert!(matches!(
+        live.add_piece(live.get_piece_info(camel).unwrap()),
+        Err(LinnyError::InvalidPiece(problems)) if problems.len() == 1
+    ));
+    assert!(live
+        .validate_piece("{}".to_string(), None)
+        .contains("isn't valid json"));
+    assert!(live
+        .validate_piece(
+            camel_json.replace("\"capture\": true", "\"capture\": 3"),
+            None
+        )
+        .contains("isn't valid json"));
+    let faster = live
+        .get_piece_info(camel)
+        .unwrap()
+        .replace("\"value\":3", "\"value\":4");
+    assert_eq!(live.validate_piece(faster.clone(), Some(camel)), "[]");
+    live.replace_piece(camel, faster).unwrap();
+    assert!(live.get_piece_info(camel).unwrap().contains("\"value\":4"));
+    live.remove_piece(camel).unwrap();
+    assert!(live.get_piece_info(camel).is_err());
+    assert_eq!(
+        live.remove_piece(camel),
+        Err(LinnyError::UnknownPieceType(camel))
+    );
+
+    let board = serde_json::from_str::<serde_json::Value>(&live.get_board()).unwrap();
+    assert_eq!(board["turn"], Color::BLACK);
+    assert_eq!(board["last_move"], "e2e4");
+    assert_eq!(board["pieces"].as_array().unwrap().len(), 32);
+    assert_eq!(board["pieces"][0]["square"], "a8");
+    assert_eq!(board["pieces"][0]["info"]["display"], "Rook");
+}
@@ -0,0 +1,386 @@
+use crate::attributes::main::MoveData;
+use crate::board::{Board, MoveError};
+use crate::error::LinnyError;
+use crate::piece::PieceType;
+
+/// Splits a square like "e4" or "b10" off the front of a string
+pub(crate) fn split_square(notation: &str) -> Option<(&str, &str)> {
+    let files = notation
+        .find(|c: char| !c.is_ascii_lowercase())
+        .unwrap_or(notation.len());
+    let ranks = notation[files..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(notation.len(), |index| index + files);
+    if files == 0 || ranks == files {
+        return None;
+    }
+    Some(notation.split_at(ranks))
+}
+
+/// Splits a square's notation into its file and rank, like "b10" into "b" and "10"
+fn file_and_rank(notation: &str) -> (&str, &str) {
+    notation.split_at(
+        notation
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(notation.len()),
+    )
+}
+
+/// Splits the promotion off the end of a move in algebraic notation, like "e8=Q", "e8Q" or "e8={7}"
+///
+/// The promotion follows the '=' if there is one, otherwise it is a piece id in braces or a letter right after the destination's rank
+fn split_promotion(san: &str) -> (&str, &str) {
+    if let Some((body, promotion)) = san.split_once('=') {
+        return (body, promotion);
+    }
+    if san.ends_with('}')
+        && let Some(start) = san.rfind('{')
+        && san[..start].ends_with(|c: char| c.is_ascii_digit())
+    {
+        return san.split_at(start);
+    }
+    let body_length = san
+        .rfind(|c: char| c.is_ascii_digit())
+        .map_or(0, |index| index + 1);
+    san.split_at(body_length)
+}
+
+impl Board {
+    /// Pawns are written without a letter in algebraic notation
+    fn is_pawn(&self, piece_type: PieceType) -> bool {
+        self.pieces[&piece_type].icon.to_ascii_lowercase() == 'p'
+    }
+
+    /// How a piece is written in notation, by uppercase icon if it is a letter no other piece uses, otherwise by id like "{7}"
+    pub(crate) fn piece_notation(&self, piece_type: PieceType) -> String {
+        let icon = self.pieces[&piece_type].icon;
+        let unique = self
+            .pieces
+            .values()
+            .filter(|info| info.icon.to_ascii_lowercase() == icon.to_ascii_lowercase())
+            .count()
+            == 1;
+        if icon.is_ascii_alphabetic() && unique {
+            icon.to_ascii_uppercase().to_string()
+        } else {
+            format!("{{{piece_type}}}")
+        }
+    }
+
+    /// Finds the piece written by `piece_notation`, ignoring case
+    fn parse_piece_notation(&self, notation: &str) -> Option<PieceType> {
+        if let Some(id) = notation
+            .strip_prefix('{')
+            .and_then(|id| id.strip_suffix('}'))
+        {
+            return id.parse().ok().filter(|id| self.pieces.contains_key(id));
+        }
+
+        let mut chars = notation.chars();
+        let (Some(icon), None) = (chars.next(), chars.next()) else {
+            return None;
+        };
+        let mut matches = self
+            .pieces
+            .iter()
+            .filter(|(_, info)| info.icon.to_ascii_lowercase() == icon.to_ascii_lowercase());
+        match (matches.next(), matches.next()) {
+            (Some((piece_type, _)), None) => Some(*piece_type),
+            _ => None,
+        }
+    }
+
+    /// Writes a move in uci style, the squares it moves from and to followed by the promotion, like "e2e4", "e7e8q" or "e1g1" for castling
+    pub(crate) fn move_to_uci(&self, move_data: &MoveData) -> String {
+        let mut notation = self.loc_to_notation(&move_data.piece.loc);
+        notation.push_str(&self.loc_to_notation(&move_data.to));
+        if let Some(promotion) = move_data.promotion {
+            notation.push_str(&self.piece_notation(promotion).to_ascii_lowercase());
+        }
+        notation
+    }
+
+    /// Finds the legal move of the side to move written by `move_to_uci`
+    pub(crate) fn parse_move(&self, notation: &str) -> Result<MoveData, LinnyError> {
+        let illegal = || LinnyError::IllegalMove(notation.to_string());
+        let (from, rest) = split_square(notation).ok_or_else(illegal)?;
+        let (to, promotion) = split_square(rest).ok_or_else(illegal)?;
+        let from = self.notation_to_loc(from)?;
+        let to = self.notation_to_loc(to)?;
+        if self
+            .check_loc(&from)
+            .map_or(false, |color| color != self.turn)
+        {
+            return Err(MoveError::WrongTurn.into());
+        }
+        let promotion = if promotion.is_empty() {
+            None
+        } else {
+            Some(self.parse_piece_notation(promotion).ok_or_else(illegal)?)
+        };
+
+        self.legal_moves(self.turn)
+            .into_iter()
+            .find(|move_data| {
+                move_data.piece.loc == from
+                    && move_data.to == to
+                    && move_data.promotion == promotion
+            })
+            .ok_or_else(illegal)
+    }
+
+    /// Writes a move in algebraic notation without a check marker, like "Nbd7", "exd6" or "e8=Q"
+    fn san_body(&self, move_data: &MoveData) -> String {
+        let from = move_data.piece.loc;
+        let to = move_data.to;
+        if move_data.castle.is_some() {
+            return if to.0 > from.0 { "O-O" } else { "O-O-O" }.to_string();
+        }
+
+        let piece_type = move_data.piece.info_index;
+        let from_notation = self.loc_to_notation(&from);
+        let (file, rank) = file_and_rank(&from_notation);
+
+        let mut san = String::new();
+        if self.is_pawn(piece_type) {
+            if move_data.capture.is_some() {
+                san.push_str(file);
+            }
+        } else {
+            san.push_str(&self.piece_notation(piece_type));
+
+            // Other pieces of the same type that can reach the square, told apart by file, then rank, then both
+            let others = self
+                .legal_moves(self.turn)
+                .into_iter()
+                .filter(|other| {
+                    other.piece.info_index == piece_type
+                        && other.to == to
+                        && other.piece.loc != from
+                        && other.castle.is_none()
+                })
+                .map(|other| other.piece.loc)
+                .collect::<Vec<_>>();
+            if others.iter().any(|loc| loc.0 == from.0) {
+                if others.iter().any(|loc| loc.1 == from.1) {
+                    san.push_str(file);
+                }
+                san.push_str(rank);
+            } else if !others.is_empty() {
+                san.push_str(file);
+            }
+        }
+
+        if move_data.capture.is_some() {
+            san.push('x');
+        }
+        san.push_str(&self.loc_to_notation(&to));
+        if let Some(promotion) = move_data.promotion {
+            san.push('=');
+            san.push_str(&self.piece_notation(promotion));
+        }
+        san
+    }
+
+    /// Writes a move in standard algebraic notation, like "Nbd7", "exd6", "O-O+" or "Qxf7#"
+    pub(crate) fn move_to_san(&self, move_data: &MoveData) -> String {
+        let mut san = self.san_body(move_data);
+        let mut after = self.clone();
+        if after.move_piece(move_data).is_ok() && after.in_check(after.turn) {
+            san.push(if after.legal_moves(after.turn).is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        san
+    }
+
+    /// Finds the legal move of the side to move written in standard algebraic notation
+    ///
+    /// Check markers, annotations, capture markers and extra disambiguation are optional,
+    /// and promotions may leave out the '=', like "e8Q"
+    pub(crate) fn san_to_move(&self, san: &str) -> Option<MoveData> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let legal_moves = self.legal_moves(self.turn);
+
+        let castle = match san {
+            "O-O" | "0-0" => Some(true),
+            "O-O-O" | "0-0-0" => Some(false),
+            _ => None,
+        };
+        if let Some(right) = castle {
+            return legal_moves.into_iter().find(|move_data| {
+                move_data.castle.is_some() && (move_data.to.0 > move_data.piece.loc.0) == right
+            });
+        }
+
+        let (body, promotion) = split_promotion(san);
+        let promotion = match promotion {
+            "" => None,
+            promotion => Some(self.parse_piece_notation(promotion)?),
+        };
+
+        let (piece_type, rest) = if let Some(rest) = body.strip_prefix('{') {
+            let (id, rest) = rest.split_once('}')?;
+            (Some(self.parse_piece_notation(&format!("{{{id}}}"))?), rest)
+        } else if body.starts_with(|c: char| c.is_ascii_uppercase()) {
+            (Some(self.parse_piece_notation(&body[..1])?), &body[1..])
+        } else {
+            (None, body)
+        };
+
+        let candidates = legal_moves
+            .into_iter()
+            .filter(|move_data| {
+                let moved = move_data.piece.info_index;
+                let matches_piece = match piece_type {
+                    Some(piece_type) => piece_type == moved,
+                    None => self.is_pawn(moved),
+                };
+                if !matches_piece || move_data.promotion != promotion || move_data.castle.is_some()
+                {
+                    return false;
+                }
+
+                let Some(from) = rest.strip_suffix(&self.loc_to_notation(&move_data.to)) else {
+                    return false;
+                };
+                let from = from.trim_end_matches(['x', '-']);
+                let from_notation = self.loc_to_notation(&move_data.piece.loc);
+                let (file, rank) = file_and_rank(&from_notation);
+                from.is_empty() || from == file || from == rank || from == from_notation
+            })
+            .collect::<Vec<_>>();
+
+        match candidates.len() {
+            1 => candidates.into_iter().next(),
+            // Prefer the move written exactly, like a pawn push over a capture with its file left out
+            _ => candidates
+                .into_iter()
+                .find(|move_data| self.san_body(move_data) == san),
+        }
+    }
+}
+
+#[test]
+fn test_uci() {
+    use crate::util::NotationError;
+
+    let mut board = Board::new(8, 8);
+    for fen in [
+        Board::DEFAULT_FEN,
+        "r3k2r/1P6/8/R7/3pP3/5N2/3K4/RN5R b k e3 0 30",
+        "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+        "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1",
+    ] {
+        board.load_fen(fen).unwrap();
+        for move_data in board.legal_moves(board.turn) {
+            assert_eq!(
+                board.parse_move(&board.move_to_uci(&move_data)),
+                Ok(move_data)
+            );
+        }
+    }
+
+    assert_eq!(board.parse_move("b7b8Q"), board.parse_move("b7b8q"));
+    for notation in ["b7b8", "b7b8k", "e1e3", "e1"] {
+        assert_eq!(
+            board.parse_move(notation),
+            Err(LinnyError::IllegalMove(notation.to_string()))
+        );
+    }
+    assert_eq!(
+        board.parse_move("e9e8"),
+        Err(LinnyError::InvalidSquare(NotationError::OutOfBounds(
+            "e9".to_string()
+        )))
+    );
+
+    board
+        .load_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+        .unwrap();
+    let castle = board.parse_move("e1g1").unwrap();
+    assert!(castle.castle.is_some());
+    assert_eq!(board.move_to_uci(&castle), "e1g1");
+}
+
+#[test]
+fn test_san() {
+    use crate::attributes::jumping::Jumping;
+    use crate::attributes::main::PieceAttribute;
+    use crate::board::PieceInfo;
+    use crate::piece::{Color, DefaultPiece};
+    use crate::util::ILoc;
+
+    let mut board = Board::new(8, 8);
+    board
+        .load_fen("1k6/4P3/8/8/4Q2Q/8/K7/7Q w - - 0 1")
+        .unwrap();
+
+    let san = |board: &Board, san: &str| board.move_to_san(&board.san_to_move(san).unwrap());
+    assert_eq!(san(&board, "Qh4e1"), "Qh4e1");
+    assert_eq!(san(&board, "Qh4-e1"), "Qh4e1");
+    assert_eq!(san(&board, "Q1h2"), "Q1h2+");
+    assert_eq!(san(&board, "Qeb7"), "Qb7#");
+    assert_eq!(san(&board, "Qxb7"), "Qb7#");
+    assert_eq!(san(&board, "e8Q"), "e8=Q+");
+    assert_eq!(san(&board, "e8=N!?"), "e8=N");
+    assert!(board.san_to_move("Qhe1").is_none());
+    assert!(board.san_to_move("Qe1").is_none());
+    assert!(board.san_to_move("e8").is_none());
+    assert!(board.san_to_move("Nc3").is_none());
+
+    // Custom pieces use their icon, or their id if the icon is taken or isn't a letter
+    let camel = PieceInfo {
+        id: 0,
+        display: "Camel".to_string(),
+        icon: 'c',
+        value: 3,
+        image_path: ["".to_string(), "".to_string()],
+        attributes: vec![PieceAttribute::Jumping(Jumping {
+            directions: vec![ILoc(1, 3), ILoc(-1, 3), ILoc(1, -3), ILoc(-1, -3)],
+            capture: true,
+            ..Default::default()
+        })],
+        royal: false,
+    };
+    let mut board = Board::new(8, 8);
+    let camel_id = board.load_piece(camel.clone());
+    let elephant_id = board.load_piece(PieceInfo { icon: 'B', ..camel });
+    let fen = format!("4k3/8/8/8/8/8/4K3/C2[{elephant_id}]4 w - - 0 1");
+    board.load_fen(&fen).unwrap();
+    assert_eq!(san(&board, "Cb4"), "Cb4");
+    assert_eq!(
+        san(&board, &format!("{{{elephant_id}}}c4")),
+        format!("{{{elephant_id}}}c4")
+    );
+    assert!(board.san_to_move("Bc4").is_none());
+    assert_eq!(board.piece_notation(camel_id), "C");
+
+    // Promotions to a piece written by id, with or without the '='
+    let pawn = board.pieces.get_mut(&DefaultPiece::PAWN).unwrap();
+    for attribute in pawn.attributes.iter_mut() {
+        if let PieceAttribute::Promotion(promotion) = attribute {
+            promotion.pieces.push(elephant_id);
+        }
+    }
+    let fen = format!("4k3/1P6/8/8/8/8/4K3/C2[{elephant_id}]4 w - - 0 1");
+    board.load_fen(&fen).unwrap();
+    let promotion = board
+        .legal_moves(Color::WHITE)
+        .into_iter()
+        .find(|move_data| move_data.promotion == Some(elephant_id))
+        .unwrap();
+    let notation = board.move_to_san(&promotion);
+    assert_eq!(notation, format!("b8={{{elephant_id}}}"));
+    assert_eq!(board.san_to_move(&notation), Some(promotion));
+    assert_eq!(
+        board.san_to_move(&format!("b8{{{elephant_id}}}")),
+        Some(promotion)
+    );
+    assert_eq!(
+        san(&board, &format!("{{{elephant_id}}}c4")),
+        format!("{{{elephant_id}}}c4")
+    );
+}
@@ -0,0 +1,312 @@
+use std::fmt;
+
+use crate::board::Board;
+use crate::cpgn::{escape, is_result, numbered_moves, parse_tag, same_piece, CpgnMetadata};
+use crate::fen::FenError;
+use crate::piece::{default_pieces, Color, DefaultPiece, PieceType};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    /// The game uses a board size or pieces that standard chess doesn't have
+    NotStandard,
+    /// A line starting with '[' isn't a valid tag pair
+    Tag(String),
+    Fen(FenError),
+    /// A comment or variation is never closed
+    Unclosed(char),
+    /// The first move that couldn't be played, counted in half moves from the start of the game
+    IllegalMove {
+        half_move: usize,
+        notation: String,
+    },
+}
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::NotStandard => {
+                write!(
+                    f,
+                    "Only standard chess on an 8x8 board can be written as pgn"
+                )
+            }
+            PgnError::Tag(line) => write!(f, "Invalid tag '{line}'"),
+            PgnError::Fen(error) => write!(f, "Invalid starting position: {error}"),
+            PgnError::Unclosed(c) => write!(f, "'{c}' is never closed"),
+            PgnError::IllegalMove {
+                half_move,
+                notation,
+            } => write!(
+                f,
+                "Move {}{} '{notation}' is illegal",
+                half_move / 2 + 1,
+                if half_move % 2 == 0 { "." } else { "..." }
+            ),
+        }
+    }
+}
+impl From<FenError> for PgnError {
+    fn from(error: FenError) -> Self {
+        PgnError::Fen(error)
+    }
+}
+
+/// Pgn lines shouldn't be longer than this
+const LINE_LENGTH: usize = 80;
+
+/// If a piece type is one of the pieces of standard chess
+fn is_default(piece_type: PieceType) -> bool {
+    piece_type <= DefaultPiece::KING
+}
+
+/// Splits move text into moves, skipping comments, variations, annotations, move numbers and the result
+fn movetext(text: &str) -> Result<Vec<String>, PgnError> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut variations = 0usize;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                if !chars.any(|c| c == '}') {
+                    return Err(PgnError::Unclosed('{'));
+                }
+            }
+            ';' => {
+                chars.any(|c| c == '\n');
+            }
+            '(' => variations += 1,
+            ')' => variations = variations.saturating_sub(1),
+            _ if variations > 0 => continue,
+            c if c.is_whitespace() => {}
+            c => {
+                token.push(c);
+                continue;
+            }
+        }
+        tokens.push(std::mem::take(&mut token));
+    }
+    tokens.push(token);
+    if variations > 0 {
+        return Err(PgnError::Unclosed('('));
+    }
+
+    Ok(tokens
+        .into_iter()
+        .filter(|token| !is_result(token) && !token.starts_with('$'))
+        .map(|token| {
+            token
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .trim_start_matches('.')
+                .trim_end_matches(['!', '?'])
+                .to_string()
+        })
+        .filter(|token| !token.is_empty())
+        .collect())
+}
+
+impl Board {
+    /// If the board and every piece on it are part of standard chess, with the rules of standard chess
+    fn is_standard(&self) -> bool {
+        let defaults = default_pieces();
+        self.width == 8
+            && self.height == 8
+            && defaults.iter().all(|(id, info)| {
+                self.pieces
+                    .get(id)
+                    .map_or(true, |own| same_piece(own, info))
+            })
+            && [Color::WHITE, Color::BLACK]
+                .iter()
+                .flat_map(|color| self.pieces_of(*color))
+                .all(|piece| is_default(piece.info_index))
+    }
+
+    /// Exports a standard chess game as pgn, with the seven tag roster and the starting position if it isn't the usual one
+    pub(crate) fn to_pgn(&self, metadata: CpgnMetadata) -> Result<String, PgnError> {
+        let mut start = self.clone();
+        while start.unmake_move().is_some() {}
+        let mut promotions = self
+            .move_history
+            .iter()
+            .filter_map(|move_data| move_data.promotion);
+        if !start.is_standard() || promotions.any(|piece_type| !is_default(piece_type)) {
+            return Err(PgnError::NotStandard);
+        }
+
+        let fen = start.to_fen();
+        let mut moves = Vec::with_capacity(self.move_history.len());
+        for move_data in self.move_history.iter() {
+            moves.push(start.move_to_san(move_data));
+            start.move_piece(move_data).unwrap();
+        }
+
+        let unknown = "?".to_string();
+        let mut pgn = String::new();
+        let mut tag = |name: &str, value: &str| {
+            pgn.push_str(&format!("[{name} \"{}\"]\n", escape(value)));
+        };
+        tag("Event", "?");
+        tag("Site", "?");
+        tag("Date", "????.??.??");
+        tag("Round", "?");
+        tag("White", metadata.white.as_ref().unwrap_or(&unknown));
+        tag("Black", metadata.black.as_ref().unwrap_or(&unknown));
+        tag("Result", metadata.result.as_deref().unwrap_or("*"));
+        if let Some(reason) = &metadata.reason {
+            tag("Termination", reason);
+        }
+        if fen != Board::DEFAULT_FEN {
+            tag("SetUp", "1");
+            tag("FEN", &fen);
+        }
+
+        let mut line = String::new();
+        pgn.push('\n');
+        for token in numbered_moves(&moves, self.start_half_moves, &metadata) {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_LENGTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        Ok(pgn)
+    }
+
+    /// Loads a standard chess game from pgn, skipping comments, annotations and variations
+    ///
+    /// The board is left untouched if anything goes wrong
+    pub(crate) fn load_pgn(&mut self, pgn: &str) -> Result<CpgnMetadata, PgnError> {
+        let mut metadata = CpgnMetadata::default();
+        let mut fen = Board::DEFAULT_FEN.to_string();
+        let mut text = String::new();
+
+        let mut lines = pgn.lines().map(str::trim).peekable();
+        while let Some(line) = lines.next_if(|line| line.is_empty() || line.starts_with('[')) {
+            if line.is_empty() {
+                continue;
+            }
+            let (name, value) = parse_tag(line).ok_or_else(|| PgnError::Tag(line.to_string()))?;
+            let known = Some(value).filter(|value| value != "?");
+            match name {
+                "White" => metadata.white = known,
+                "Black" => metadata.black = known,
+                "Result" => metadata.result = known.filter(|result| result != "*"),
+                "Termination" => metadata.reason = known,
+                "FEN" => fen = known.unwrap_or(fen),
+                _ => {}
+            }
+        }
+        for line in lines.filter(|line| !line.starts_with('%')) {
+            text.push_str(line);
+            text.push('\n');
+        }
+
+        let mut board = self.clone();
+        board.load_fen(&fen)?;
+        if !board.is_standard() {
+            return Err(PgnError::NotStandard);
+        }
+
+        for (i, san) in movetext(&text)?.into_iter().enumerate() {
+            let half_move = board.start_half_moves + i;
+            let illegal = || PgnError::IllegalMove {
+                half_move,
+                notation: san.clone(),
+            };
+            let move_data = board.san_to_move(&san).ok_or_else(illegal)?;
+            board.move_piece(&move_data).map_err(|_| illegal())?;
+        }
+
+        *self = board;
+        Ok(metadata)
+    }
+}
+
+#[test]
+fn test_pgn() {
+    use crate::board::GameStatus;
+
+    let pgn = "[Event \"Casual game\"]
+[White \"Alice\"]
+[Black \"Bob\"]
+[Result \"1-0\"]
+
+1. e4 {The king's pawn} e5 2. Bc4 Nc6 (2... Nf6 3. d3 (3. Qh5?) Nc6) 3. Qh5 $2
+Nf6?? ; Missing the threat
+4. Qxf7# 1-0";
+    let mut board = Board::new(8, 8);
+    let metadata = board.load_pgn(pgn).unwrap();
+    assert_eq!(metadata.white.as_deref(), Some("Alice"));
+    assert_eq!(metadata.result.as_deref(), Some("1-0"));
+    assert_eq!(board.status(), GameStatus::Checkmate(Color::WHITE));
+
+    let exported = board.to_pgn(metadata).unwrap();
+    assert!(exported.starts_with("[Event \"?\"]\n[Site \"?\"]"));
+    assert!(!exported.contains("[FEN"));
+    assert!(exported.ends_with("\n\n1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"));
+
+    // Disambiguation, castling, promotion and en passant
+    let fen = "r3k2r/1P6/8/R7/3pP3/5N2/3K4/RN5R b k e3 0 30";
+    let mut board = Board::new(8, 8);
+    board.load_fen(fen).unwrap();
+    let moves = [
+        "dxe3+", "Kxe3", "O-O", "bxa8=Q", "Kg7", "Nbd2", "Kg8", "Rad1",
+    ];
+    for san in moves {
+        let move_data = board.san_to_move(san).unwrap();
+        assert_eq!(board.move_to_san(&move_data), san);
+        board.move_piece(&move_data).unwrap();
+    }
+
+    let exported = board.to_pgn(CpgnMetadata::default()).unwrap();
+    assert!(exported.contains(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]")));
+    assert!(exported.contains("30... dxe3+ 31. Kxe3 O-O 32. bxa8=Q Kg7 33. Nbd2 Kg8 34. Rad1 *"));
+    let mut loaded = Board::new(8, 8);
+    loaded.load_pgn(&exported).unwrap();
+    assert_eq!(loaded.to_fen(), board.to_fen());
+
+    let mut board = Board::new(8, 8);
+    board.load_fen("1k6/8/8/8/4Q2Q/8/K7/7Q w - - 0 1").unwrap();
+    assert!(board.san_to_move("Qh4e1").is_some());
+    assert!(board.san_to_move("Qhe1").is_none());
+    assert!(board.san_to_move("Q1h2+").is_some());
+
+    assert_eq!(
+        Board::new(8, 8).load_pgn("1. e4 e5 2. Ke3"),
+        Err(PgnError::IllegalMove {
+            half_move: 2,
+            notation: "Ke3".to_string()
+        })
+    );
+    assert_eq!(
+        Board::new(8, 8).load_pgn("1. e4 (1. d4 e5"),
+        Err(PgnError::Unclosed('('))
+    );
+    board.resize(10, 8);
+    assert_eq!(
+        board.to_pgn(CpgnMetadata::default()),
+        Err(PgnError::NotStandard)
+    );
+
+    // Default pieces with other rules aren't standard chess, even with the usual ids
+    let mut board = Board::new(8, 8);
+    board.load_fen(Board::DEFAULT_FEN).unwrap();
+    board
+        .pieces
+        .get_mut(&DefaultPiece::KNIGHT)
+        .unwrap()
+        .attributes
+        .clear();
+    assert_eq!(
+        board.to_pgn(CpgnMetadata::default()),
+        Err(PgnError::NotStandard)
+    );
+    assert_eq!(board.load_pgn("1. e4 e5"), Err(PgnError::NotStandard));
+}
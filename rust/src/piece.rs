@@ -2,11 +2,14 @@ use bit_vec::BitVec;
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 
+use crate::attributes::castle::Castle;
 use crate::attributes::enpassant::EnPassant;
 use crate::attributes::jumping::Jumping;
-use crate::attributes::main::{MoveData, PieceAttributeTrait};
+use crate::attributes::main::{MoveData, PieceAttribute, PieceAttributeTrait};
+use crate::attributes::promotion::Promotion;
 use crate::attributes::sliding::Sliding;
 use crate::board::{Board, PieceInfo};
+use crate::error::LinnyError;
 use crate::hashmap;
 use crate::util::{ILoc, Loc};
 
@@ -15,6 +18,14 @@ pub(crate) struct Color(usize);
 impl Color {
     pub(crate) const WHITE: usize = 0;
     pub(crate) const BLACK: usize = 1;
+
+    /// Rejects anything but white (0) or black (1), for colors coming from outside the engine
+    pub(crate) fn checked(color: usize) -> Result<ColorType, LinnyError> {
+        match color {
+            Color::WHITE | Color::BLACK => Ok(color),
+            _ => Err(LinnyError::InvalidColor(color)),
+        }
+    }
 }
 pub(crate) type ColorType = usize;
 
@@ -26,7 +37,9 @@ impl ColorTrait for ColorType {
         match *self {
             Color::WHITE => Color::BLACK,
             Color::BLACK => Color::WHITE,
-            _ => panic!("Invalid color"),
+            color => unreachable!(
+                "{color} is not a color, colors from outside are checked with Color::checked"
+            ),
         }
     }
 }
@@ -72,9 +85,23 @@ impl Piece {
         for attribute in info.attributes.iter() {
             attribute.moves(board, self, &mut moves);
         }
+        for attribute in info.attributes.iter() {
+            if let PieceAttribute::Promotion(promotion) = attribute {
+                promotion.promote(board, self, &mut moves);
+            }
+        }
         moves
     }
 
+    /// Moves that do not leave a royal piece of the same color under attack
+    pub(crate) fn legal_moves(&self, board: &Board) -> Vec<MoveData> {
+        let mut scratch = board.clone();
+        self.moves(board)
+            .into_iter()
+            .filter(|move_data| scratch.try_move(move_data))
+            .collect()
+    }
+
     pub(crate) fn attacks(&self, board: &Board) -> BitVec {
         let info = self.info(board);
         let mut attacks = BitVec::from_elem(board.width * board.height, false);
@@ -93,6 +120,7 @@ pub(crate) fn default_pieces() -> FxHashMap<PieceType, PieceInfo> {
             icon: 'p',
             value: 1,
             image_path: ["bp.png".to_string(), "wp.png".to_string()],
+            royal: false,
             attributes: vec![
                 Jumping {
                     black_directions: Some(vec![ILoc(0, 1)]),
@@ -100,6 +128,7 @@ pub(crate) fn default_pieces() -> FxHashMap<PieceType, PieceInfo> {
                     capture: false,
                     capture_only: false,
                     first_move_only: false,
+                    blockable: false,
                 }.into(),
                 Jumping {
                     black_directions: Some(vec![ILoc(0, 2)]),
@@ -107,6 +136,7 @@ pub(crate) fn default_pieces() -> FxHashMap<PieceType, PieceInfo> {
                     capture: false,
                     capture_only: false,
                     first_move_only: true,
+                    blockable: true,
                 }.into(),
                 Jumping {
                     black_directions: Some(vec![ILoc(1, 1), ILoc(-1, 1)]),
@@ -114,14 +144,26 @@ pub(crate) fn default_pieces() -> FxHashMap<PieceType, PieceInfo> {
                     capture: true,
                     capture_only: true,
                     first_move_only: false,
+                    blockable: false,
                 }.into(),
                 EnPassant {
                     offsets: vec![ILoc(1, 0), ILoc(-1, 0)],
                     black_offsets: Some(vec![ILoc(1, 0), ILoc(-1, 0)]),
-                    capture_offset: ILoc(0, 1),
-                    black_capture_offset: Some(ILoc(0, -1)),
+                    capture_offset: ILoc(0, -1),
+                    black_capture_offset: Some(ILoc(0, 1)),
                     piece: DefaultPiece::PAWN,
-                }.into()
+                }.into(),
+                Promotion {
+                    rows: vec![0],
+                    black_rows: None,
+                    pieces: vec![
+                        DefaultPiece::QUEEN,
+                        DefaultPiece::ROOK,
+                        DefaultPiece::BISHOP,
+                        DefaultPiece::KNIGHT,
+                    ],
+                    optional: false,
+                }.into(),
             ],
         },
         DefaultPiece::BISHOP => PieceInfo {
@@ -130,6 +172,7 @@ pub(crate) fn default_pieces() -> FxHashMap<PieceType, PieceInfo> {
             icon: 'b',
             value: 3,
             image_path: ["bb.png".to_string(), "wb.png".to_string()],
+            royal: false,
             attributes: vec![
                 Sliding {
                     directions: vec![ILoc(1, 1), ILoc(-1, 1), ILoc(1, -1), ILoc(-1, -1)],
@@ -145,6 +188,7 @@ pub(crate) fn default_pieces() -> FxHashMap<PieceType, PieceInfo> {
             icon: 'n',
             value: 3,
             image_path: ["bn.png".to_string(), "wn.png".to_string()],
+            royal: false,
             attributes: vec![
                 Jumping {
                     directions: vec![
@@ -161,6 +205,7 @@ pub(crate) fn default_pieces() -> FxHashMap<PieceType, PieceInfo> {
                     capture: true,
                     capture_only: false,
                     first_move_only: false,
+                    blockable: false,
                 }.into()
             ],
         },
@@ -170,6 +215,7 @@ pub(crate) fn default_pieces() -> FxHashMap<PieceType, PieceInfo> {
             icon: 'r',
             value: 5,
             image_path: ["br.png".to_string(), "wr.png".to_string()],
+            royal: false,
             attributes: vec![
                 Sliding {
                     directions: vec![ILoc(1, 0), ILoc(-1, 0), ILoc(0, 1), ILoc(0, -1)],
@@ -185,6 +231,7 @@ pub(crate) fn default_pieces() -> FxHashMap<PieceType, PieceInfo> {
             icon: 'q',
             value: 9,
             image_path: ["bq.png".to_string(), "wq.png".to_string()],
+            royal: false,
             attributes: vec![
                 Sliding {
                     directions: vec![
@@ -209,6 +256,7 @@ pub(crate) fn default_pieces() -> FxHashMap<PieceType, PieceInfo> {
             icon: 'k',
             value: 0,
             image_path: ["bk.png".to_string(), "wk.png".to_string()],
+            royal: true,
             attributes: vec![
                 Jumping {
                     directions: vec![
@@ -225,6 +273,16 @@ pub(crate) fn default_pieces() -> FxHashMap<PieceType, PieceInfo> {
                     capture: true,
                     capture_only: false,
                     first_move_only: false,
+                    blockable: false,
+                }.into(),
+                Castle {
+                    destinations: vec![ILoc(2, 0), ILoc(-2, 0)],
+                    black_destinations: None,
+                    rook: vec![ILoc(3, 0), ILoc(-4, 0)],
+                    black_rook: None,
+                    rook_destination: vec![ILoc(1, 0), ILoc(-1, 0)],
+                    black_rook_destination: None,
+                    rook_piece: DefaultPiece::ROOK,
                 }.into(),
             ],
         },
@@ -0,0 +1,332 @@
+use bit_vec::BitVec;
+
+use crate::attributes::main::{OptionValue, PieceAttributeTrait};
+use crate::board::{Board, PieceInfo};
+use crate::error::LinnyError;
+use crate::piece::{Color, ColorTrait, PieceType};
+
+impl Board {
+    /// Registers a piece under its own id, or a new one if that id is taken, returning the id used
+    pub(crate) fn load_piece(&mut self, info: PieceInfo) -> PieceType {
+        let id = if self.pieces.contains_key(&info.id) {
+            self.next_piece_id
+        } else {
+            info.id
+        };
+        self.load_piece_as(id, info);
+        id
+    }
+
+    /// Registers a piece under a given id, like one stored in a cpgn
+    pub(crate) fn load_piece_as(&mut self, id: PieceType, mut info: PieceInfo) {
+        info.id = id;
+        self.next_piece_id = self.next_piece_id.max(id + 1);
+        self.pieces.insert(id, info);
+        for locations in self.piece_locations.iter_mut() {
+            locations
+                .entry(id)
+                .or_insert_with(|| BitVec::from_elem(self.width * self.height, false));
+        }
+        self.zobrist.add_piece(id);
+    }
+
+    /// Unregisters a piece, taking every piece of its type off the board
+    ///
+    /// Its id isn't given to another piece, and promotions to it are dropped.
+    /// The game history is forgotten if the piece is on the board or was part of it, as those moves can't be undone any more
+    pub(crate) fn remove_piece(&mut self, id: PieceType) -> Result<PieceInfo, LinnyError> {
+        self.piece_info(id)?;
+        let changed = self.squares_of(id);
+        if !changed.is_empty() || self.in_history(id) {
+            self.forget_history();
+        }
+
+        for color in [Color::WHITE, Color::BLACK] {
+            self.piece_locations[color].remove(&id);
+            self.zobrist.pieces[color].remove(&id);
+            for index in changed.iter() {
+                self.general_locations[color].set(*index, false);
+                self.first_moves[color].set(*index, false);
+            }
+        }
+        let info = self.pieces.remove(&id).unwrap();
+        self.remap_references(|piece_type| (piece_type != id).then_some(piece_type));
+
+        self.position_hash = self.full_position_hash();
+        self.hashes.clear();
+        self.record_position();
+        self.refresh_attacks(&changed);
+        Ok(info)
+    }
+
+    /// Swaps the rules of a piece for new ones, keeping its id and every piece of its type on the board
+    ///
+    /// Like with `remove_piece`, the game history is forgotten if the piece is on the board or was part of it,
+    /// as its moves may not be possible with the new rules
+    pub(crate) fn replace_piece(
+        &mut self,
+        id: PieceType,
+        mut info: PieceInfo,
+    ) -> Result<(), LinnyError> {
+        self.piece_info(id)?;
+        let changed = self.squares_of(id);
+        if !changed.is_empty() || self.in_history(id) {
+            self.forget_history();
+        }
+        info.id = id;
+        self.pieces.insert(id, info);
+
+        self.refresh_attacks(&changed);
+        Ok(())
+    }
+
+    /// Moves a piece to a new id, updating the board, the game history and every piece that refers to it
+    pub(crate) fn remap_piece(&mut self, from: PieceType, to: PieceType) -> Result<(), LinnyError> {
+        self.piece_info(from)?;
+        if from == to {
+            return Ok(());
+        }
+        if self.pieces.contains_key(&to) {
+            return Err(LinnyError::PieceIdTaken(to));
+        }
+
+        let mut info = self.pieces.remove(&from).unwrap();
+        info.id = to;
+        self.pieces.insert(to, info);
+        self.next_piece_id = self.next_piece_id.max(to + 1);
+        self.zobrist.add_piece(to);
+        for color in [Color::WHITE, Color::BLACK] {
+            let locations = self.piece_locations[color].remove(&from).unwrap();
+            self.piece_locations[color].insert(to, locations);
+            self.zobrist.pieces[color].remove(&from);
+        }
+
+        let remap = |piece_type: PieceType| if piece_type == from { to } else { piece_type };
+        self.remap_references(|piece_type| Some(remap(piece_type)));
+        for move_data in self.move_history.iter_mut() {
+            move_data.piece.info_index = remap(move_data.piece.info_index);
+            move_data.promotion = move_data.promotion.map(remap);
+        }
+        for undo in self.undo_history.iter_mut() {
+            if let Some(captured) = &mut undo.captured {
+                captured.info_index = remap(captured.info_index);
+            }
+        }
+        if let Some((_, piece)) = &mut self.start_en_passant {
+            piece.info_index = remap(piece.info_index);
+        }
+
+        // The hashes of earlier positions used the old keys, so they are worked out again by replaying the game.
+        // The moves were already played, so they aren't checked again
+        let mut undone = Vec::with_capacity(self.move_history.len());
+        while let Some(move_data) = self.unmake_move() {
+            undone.push(move_data);
+        }
+        self.position_hash = self.full_position_hash();
+        self.hashes.clear();
+        self.record_position();
+        for move_data in undone.into_iter().rev() {
+            let undo = self.raw_move(&move_data)?;
+            self.move_history.push(move_data);
+            self.undo_history.push(undo);
+            self.turn = self.turn.other();
+            self.record_position();
+        }
+        Ok(())
+    }
+
+    /// Changes every reference to a piece in the attributes of other pieces, dropping it from lists if `remap` returns None
+    fn remap_references(&mut self, remap: impl Fn(PieceType) -> Option<PieceType>) {
+        for info in self.pieces.values_mut() {
+            for attribute in info.attributes.iter_mut() {
+                for option in attribute.info().options {
+                    let value = match attribute.get_option(option.name) {
+                        Some(OptionValue::DefaultPiece(piece_type)) => {
+                            remap(piece_type).map(OptionValue::DefaultPiece)
+                        }
+                        Some(OptionValue::PieceVec(pieces)) => Some(OptionValue::PieceVec(
+                            pieces.into_iter().filter_map(&remap).collect(),
+                        )),
+                        _ => None,
+                    };
+                    if value.is_some() {
+                        attribute.set_option(option.name, &value).unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    /// The squares pieces of the given type are on
+    fn squares_of(&self, id: PieceType) -> Vec<usize> {
+        let mut squares = vec![];
+        for color in [Color::WHITE, Color::BLACK] {
+            if let Some(locations) = self.piece_locations[color].get(&id) {
+                squares.extend(
+                    locations
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, bit)| *bit)
+                        .map(|(index, _)| index),
+                );
+            }
+        }
+        squares
+    }
+
+    /// If a piece of the given type moved, was captured or was promoted to in the game so far
+    fn in_history(&self, id: PieceType) -> bool {
+        let moved = self
+            .move_history
+            .iter()
+            .any(|move_data| move_data.piece.info_index == id || move_data.promotion == Some(id));
+        let captured = self
+            .undo_history
+            .iter()
+            .any(|undo| undo.captured.map_or(false, |piece| piece.info_index == id));
+        moved || captured
+    }
+
+    /// Makes the current position the starting one, so the moves that led to it can't be undone
+    fn forget_history(&mut self) {
+        self.start_en_passant = self.en_passant_square().zip(self.en_passant_target());
+        self.start_half_moves = self.half_moves();
+        self.move_history.clear();
+        self.undo_history.clear();
+        self.hashes.clear();
+        self.record_position();
+    }
+}
+
+#[test]
+fn test_registry() {
+    use crate::attributes::jumping::Jumping;
+    use crate::attributes::main::PieceAttribute;
+    use crate::attributes::promotion::Promotion;
+    use crate::piece::DefaultPiece;
+    use crate::util::{ILoc, Loc};
+
+    let camel = PieceInfo {
+        id: 20,
+        display: "Camel".to_string(),
+        icon: 'c',
+        value: 3,
+        image_path: ["".to_string(), "".to_string()],
+        attributes: vec![PieceAttribute::Jumping(Jumping {
+            directions: vec![ILoc(1, 3), ILoc(-1, 3), ILoc(1, -3), ILoc(-1, -3)],
+            capture: true,
+            ..Default::default()
+        })],
+        royal: false,
+    };
+
+    // Ids are kept if they are free, and never handed out twice
+    let mut board = Board::new(8, 8);
+    assert_eq!(board.load_piece(camel.clone()), 20);
+    assert_eq!(
+        board.load_piece(PieceInfo {
+            icon: 'z',
+            ..camel.clone()
+        }),
+        21
+    );
+    assert_eq!(board.pieces[&21].id, 21);
+    board.remove_piece(21).unwrap();
+    assert_eq!(
+        board.load_piece(PieceInfo {
+            id: 0,
+            icon: 'y',
+            ..camel.clone()
+        }),
+        22
+    );
+    assert_eq!(
+        board.remove_piece(21).unwrap_err(),
+        LinnyError::UnknownPieceType(21)
+    );
+
+    // Moving a piece to a new id keeps the game and its hashes intact
+    board.load_fen("4k3/8/8/8/8/8/8/C3K3 w - - 0 1").unwrap();
+    let promotions = |board: &Board| {
+        board.pieces[&DefaultPiece::PAWN]
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                PieceAttribute::Promotion(Promotion { pieces, .. }) => Some(pieces.clone()),
+                _ => None,
+            })
+            .unwrap()
+    };
+    let mut promotion = promotions(&board);
+    promotion.push(20);
+    board
+        .pieces
+        .get_mut(&DefaultPiece::PAWN)
+        .unwrap()
+        .attributes
+        .iter_mut()
+        .for_each(|attribute| {
+            if let PieceAttribute::Promotion(value) = attribute {
+                value.pieces = promotion.clone();
+            }
+        });
+    for _ in 0..2 {
+        for notation in ["a1b4", "e8d8", "b4a1", "d8e8"] {
+            board
+                .move_piece(&board.parse_move(notation).unwrap())
+                .unwrap();
+        }
+    }
+    let fen = board.to_fen();
+    board.remap_piece(20, 30).unwrap();
+    assert_eq!(board.to_fen(), fen);
+    assert_eq!(board.position_hash, board.full_position_hash());
+    assert_eq!(board.hashes[&board.hash()], 3);
+    assert!(promotions(&board).contains(&30));
+    assert_eq!(board.move_history[0].piece.info_index, 30);
+    assert!(board.unmake_move().is_some());
+    assert_eq!(
+        board.remap_piece(30, DefaultPiece::KING),
+        Err(LinnyError::PieceIdTaken(DefaultPiece::KING))
+    );
+
+    // Removing a piece from the game forgets the history it was part of
+    board.remove_piece(30).unwrap();
+    assert!(board.move_history.is_empty());
+    assert_eq!(board.half_moves(), 7);
+    assert!(!promotions(&board).contains(&30));
+    assert_eq!(board.position_hash, board.full_position_hash());
+
+    // Replacing a piece changes what it attacks without moving it
+    let mut board = Board::new(8, 8);
+    let id = board.load_piece(camel.clone());
+    board.load_fen("4k3/8/8/8/8/8/8/C3K3 w - - 0 1").unwrap();
+    let b4 = board.loc_as_bit(&board.notation_to_loc("b4").unwrap());
+    assert!(board.attacks[Color::WHITE].get(b4).contains(&true));
+    let mut knight = camel;
+    knight.attributes = vec![PieceAttribute::Jumping(Jumping {
+        directions: vec![ILoc(1, 2), ILoc(2, 1)],
+        capture: true,
+        ..Default::default()
+    })];
+    board.replace_piece(id, knight).unwrap();
+    assert!(!board.attacks[Color::WHITE].get(b4).contains(&true));
+    assert_eq!(board.pieces_of(Color::WHITE).len(), 2);
+
+    // Replacing a piece that was part of the game forgets moves the new rules may not allow
+    let mut board = Board::new(8, 8);
+    board.load_fen(Board::DEFAULT_FEN).unwrap();
+    board
+        .move_piece(&board.parse_move("e2e4").unwrap())
+        .unwrap();
+    let mut pawn = board.pieces[&DefaultPiece::PAWN].clone();
+    pawn.attributes.retain(|attribute| {
+        !matches!(attribute, PieceAttribute::Jumping(jumping) if jumping.first_move_only)
+    });
+    board.replace_piece(DefaultPiece::PAWN, pawn).unwrap();
+    assert!(board.move_history.is_empty());
+    assert_eq!(board.half_moves(), 1);
+    board.remap_piece(DefaultPiece::PAWN, 20).unwrap();
+    assert_eq!(board.get(&Loc(4, 4)).unwrap().info_index, 20);
+    assert_eq!(board.position_hash, board.full_position_hash());
+}
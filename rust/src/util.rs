@@ -1,22 +1,66 @@
-use std::ops;
+use std::{fmt, ops};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    /// The square doesn't start with a lowercase file like "a" or "aa"
+    MissingFile(String),
+    /// The file isn't followed by a rank of 1 or more, and nothing else
+    InvalidRank(String),
+    /// The square is valid notation, but isn't on the board
+    OutOfBounds(String),
+}
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotationError::MissingFile(notation) => {
+                write!(f, "'{notation}' doesn't start with a file like 'a'")
+            }
+            NotationError::InvalidRank(notation) => {
+                write!(f, "'{notation}' doesn't end with a rank like '1'")
+            }
+            NotationError::OutOfBounds(notation) => write!(f, "'{notation}' is not on the board"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub(crate) struct Loc(pub(crate) usize, pub(crate) usize);
 impl Loc {
-    pub(crate) fn from_notation(notation: &str) -> Self {
-        let mut chars = notation.chars();
-        let file = chars.next().unwrap() as usize - 'a' as usize;
-        let rank = chars.next().unwrap() as usize - '1' as usize;
-        Self(file, rank)
+    /// Parses notation like "e4", "b10" or "aa3", files going a..z then aa, ab..
+    pub(crate) fn from_notation(notation: &str) -> Result<Self, NotationError> {
+        let letters = notation
+            .find(|c: char| !c.is_ascii_lowercase())
+            .unwrap_or(notation.len());
+        let (file, rank) = notation.split_at(letters);
+        if file.is_empty() {
+            return Err(NotationError::MissingFile(notation.to_string()));
+        }
+
+        let file = file
+            .bytes()
+            .try_fold(0usize, |file, c| {
+                file.checked_mul(26)?.checked_add((c - b'a') as usize + 1)
+            })
+            .ok_or_else(|| NotationError::MissingFile(notation.to_string()))?;
+        let rank = Some(rank)
+            .filter(|rank| !rank.starts_with('0') && rank.bytes().all(|c| c.is_ascii_digit()))
+            .and_then(|rank| rank.parse::<usize>().ok())
+            .ok_or_else(|| NotationError::InvalidRank(notation.to_string()))?;
+        Ok(Self(file - 1, rank - 1))
     }
 
     pub(crate) fn as_notation(&self) -> String {
-        let mut notation = String::new();
-        notation.push((self.0 as u8 + b'a') as char);
-        notation.push((self.1 as u8 + b'1') as char);
-        notation
+        let mut file = vec![];
+        let mut remaining = self.0 + 1;
+        while remaining > 0 {
+            remaining -= 1;
+            file.push(b'a' + (remaining % 26) as u8);
+            remaining /= 26;
+        }
+        file.reverse();
+        format!("{}{}", String::from_utf8(file).unwrap(), self.1 + 1)
     }
 
     #[allow(non_snake_case)]
@@ -40,14 +84,43 @@ impl ILoc {
 #[test]
 fn test_loc_notation() {
     // From notation
-    assert_eq!(Loc::from_notation("a1"), Loc(0, 0));
-    assert_eq!(Loc::from_notation("h8"), Loc(7, 7));
-    assert_eq!(Loc::from_notation("e4"), Loc(4, 3));
+    assert_eq!(Loc::from_notation("a1"), Ok(Loc(0, 0)));
+    assert_eq!(Loc::from_notation("h8"), Ok(Loc(7, 7)));
+    assert_eq!(Loc::from_notation("e4"), Ok(Loc(4, 3)));
+    assert_eq!(Loc::from_notation("e10"), Ok(Loc(4, 9)));
+    assert_eq!(Loc::from_notation("z1"), Ok(Loc(25, 0)));
+    assert_eq!(Loc::from_notation("aa3"), Ok(Loc(26, 2)));
+    assert_eq!(Loc::from_notation("ba12"), Ok(Loc(52, 11)));
 
     // To notation
     assert_eq!(Loc(0, 0).as_notation(), "a1");
     assert_eq!(Loc(7, 7).as_notation(), "h8");
     assert_eq!(Loc(4, 3).as_notation(), "e4");
+    assert_eq!(Loc(4, 9).as_notation(), "e10");
+    assert_eq!(Loc(26, 2).as_notation(), "aa3");
+    assert_eq!(Loc(701, 0).as_notation(), "zz1");
+    assert_eq!(Loc(702, 0).as_notation(), "aaa1");
+
+    // Bad notation
+    for (notation, error) in [
+        (
+            "",
+            NotationError::MissingFile as fn(String) -> NotationError,
+        ),
+        ("4e", NotationError::MissingFile),
+        ("E4", NotationError::MissingFile),
+        ("e", NotationError::InvalidRank),
+        ("e0", NotationError::InvalidRank),
+        ("e04", NotationError::InvalidRank),
+        ("e+4", NotationError::InvalidRank),
+        ("e4x", NotationError::InvalidRank),
+        ("e99999999999999999999", NotationError::InvalidRank),
+    ] {
+        assert_eq!(
+            Loc::from_notation(notation),
+            Err(error(notation.to_string()))
+        );
+    }
 }
 
 impl From<Loc> for ILoc {
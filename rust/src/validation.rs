@@ -0,0 +1,213 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::attributes::main::{OptionValue, PieceAttributeTrait};
+use crate::board::{Board, PieceInfo};
+use crate::piece::PieceType;
+
+/// Something wrong with a piece, found before it is loaded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PieceProblem {
+    /// The index of the attribute the problem is with, None if it is with the piece itself
+    pub(crate) attribute: Option<usize>,
+    /// The option the problem is with, if it is about a single option
+    pub(crate) option: Option<String>,
+    pub(crate) message: String,
+}
+impl PieceProblem {
+    fn piece(message: String) -> Self {
+        Self {
+            attribute: None,
+            option: None,
+            message,
+        }
+    }
+
+    /// The piece couldn't be read at all
+    pub(crate) fn json(error: serde_json::Error) -> Self {
+        Self::piece(format!("The piece isn't valid json: {error}"))
+    }
+}
+impl fmt::Display for PieceProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl OptionValue {
+    fn is_empty(&self) -> bool {
+        match self {
+            OptionValue::ILocVec(value) => value.is_empty(),
+            OptionValue::NumberVec(value) => value.is_empty(),
+            OptionValue::PieceVec(value) => value.is_empty(),
+            _ => false,
+        }
+    }
+
+    fn pieces(&self) -> Vec<PieceType> {
+        match self {
+            OptionValue::DefaultPiece(piece_type) => vec![*piece_type],
+            OptionValue::PieceVec(pieces) => pieces.clone(),
+            _ => vec![],
+        }
+    }
+}
+
+impl Board {
+    /// Checks a piece before it is loaded, returning every problem found in a way the piece editor can show
+    ///
+    /// Options are checked against their `InfoOption`, then each attribute adds its own problems.
+    /// A piece replacing another one may reuse its icon
+    pub(crate) fn validate_piece(
+        &self,
+        info: &PieceInfo,
+        replacing: Option<PieceType>,
+    ) -> Vec<PieceProblem> {
+        let mut problems = vec![];
+
+        if info.display.trim().is_empty() {
+            problems.push(PieceProblem::piece(
+                "The piece needs a name to display.".to_string(),
+            ));
+        }
+        if let Some(other) = self.pieces.values().find(|other| {
+            Some(other.id) != replacing
+                && other.icon.to_ascii_lowercase() == info.icon.to_ascii_lowercase()
+        }) {
+            problems.push(PieceProblem::piece(format!(
+                "The icon '{}' is already used by the {}.",
+                info.icon, other.display
+            )));
+        }
+        if info.attributes.is_empty() {
+            problems.push(PieceProblem::piece(
+                "The piece has no attributes, so it can never move.".to_string(),
+            ));
+        }
+
+        for (index, attribute) in info.attributes.iter().enumerate() {
+            let attribute_info = attribute.info();
+            let mut problem = |option: Option<&str>, message: String| {
+                problems.push(PieceProblem {
+                    attribute: Some(index),
+                    option: option.map(str::to_string),
+                    message: format!("{}: {message}", attribute_info.name),
+                });
+            };
+
+            for option in attribute_info.options.iter() {
+                let name = Some(option.name);
+                let Some(value) = attribute.get_option(option.name) else {
+                    if !option.optional {
+                        problem(
+                            name,
+                            format!("'{}' is required. {}", option.name, option.description),
+                        );
+                    }
+                    continue;
+                };
+
+                // Values of the wrong type are already turned away when the piece's json is read
+                if value.is_empty() {
+                    problem(
+                        name,
+                        format!(
+                            "'{}' needs at least one value. {}",
+                            option.name, option.description
+                        ),
+                    );
+                }
+                for piece_type in value.pieces() {
+                    if !self.pieces.contains_key(&piece_type) {
+                        problem(
+                            name,
+                            format!(
+                                "'{}' refers to piece {piece_type}, which doesn't exist.",
+                                option.name
+                            ),
+                        );
+                    }
+                }
+            }
+
+            for message in attribute.problems() {
+                problem(None, message);
+            }
+        }
+
+        problems
+    }
+}
+
+#[test]
+fn test_validate_piece() {
+    use crate::attributes::enpassant::EnPassant;
+    use crate::attributes::jumping::Jumping;
+    use crate::attributes::main::PieceAttribute;
+    use crate::piece::DefaultPiece;
+    use crate::util::ILoc;
+
+    let board = Board::new(8, 8);
+    let mut camel = PieceInfo {
+        id: 0,
+        display: "Camel".to_string(),
+        icon: 'c',
+        value: 3,
+        image_path: ["".to_string(), "".to_string()],
+        attributes: vec![PieceAttribute::Jumping(Jumping {
+            directions: vec![ILoc(1, 3), ILoc(-1, 3), ILoc(1, -3), ILoc(-1, -3)],
+            capture: true,
+            ..Default::default()
+        })],
+        royal: false,
+    };
+    assert_eq!(board.validate_piece(&camel, None), vec![]);
+
+    // Every default piece is valid, apart from sharing an icon with itself
+    for piece_type in [DefaultPiece::PAWN, DefaultPiece::KING] {
+        let info = &board.pieces[&piece_type];
+        let problems = board.validate_piece(info, None);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.contains("already used"));
+        assert_eq!(board.validate_piece(info, Some(piece_type)), vec![]);
+    }
+
+    camel.icon = 'N';
+    camel.attributes = vec![
+        PieceAttribute::Jumping(Jumping {
+            capture_only: true,
+            ..Default::default()
+        }),
+        PieceAttribute::EnPassant(EnPassant {
+            offsets: vec![ILoc(1, -1)],
+            piece: 99,
+            ..Default::default()
+        }),
+    ];
+    let problems = board.validate_piece(&camel, None);
+    let summary = problems
+        .iter()
+        .map(|problem| (problem.attribute, problem.option.as_deref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (None, None),
+            (Some(0), Some("directions")),
+            (Some(0), None),
+            (Some(1), Some("piece")),
+        ]
+    );
+    assert_eq!(
+        problems[0].message,
+        "The icon 'N' is already used by the Knight."
+    );
+    assert!(problems[1]
+        .message
+        .starts_with("Jumping: 'directions' needs at least one value."));
+    assert_eq!(
+        problems[3].message,
+        "En passant: 'piece' refers to piece 99, which doesn't exist."
+    );
+}
@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+
+use crate::attributes::main::PieceAttribute;
+use crate::attributes::promotion::Promotion;
+use crate::board::{Board, PieceInfo, WinCondition};
+use crate::error::LinnyError;
+use crate::piece::{default_pieces, PieceType};
+
+/// Which piece promotes, and how, replacing any promotion the piece already has
+///
+/// A piece can be given several rules, which all apply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "bare"), derive(schemars::JsonSchema))]
+pub(crate) struct PromotionRule {
+    pub(crate) piece: PieceType,
+    #[serde(flatten)]
+    pub(crate) promotion: Promotion,
+}
+
+/// Everything needed to set up a game of a variant, shared as a json or toml file
+///
+/// ```toml
+/// name = "Mini chess"
+/// width = 5
+/// height = 5
+/// fen = "rnbqk/ppppp/5/PPPPP/RNBQK w - - 0 1"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "bare"), derive(schemars::JsonSchema))]
+pub(crate) struct Variant {
+    pub(crate) name: String,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    /// If the pieces of standard chess are available, pieces with the same id replace them
+    #[serde(default = "default_true")]
+    pub(crate) default_pieces: bool,
+    #[serde(default)]
+    pub(crate) pieces: Vec<PieceInfo>,
+    /// The starting position
+    pub(crate) fen: String,
+    /// The ids of the pieces that must not be left under attack, the `royal` flag of each piece if not given
+    #[serde(default)]
+    pub(crate) royal: Option<Vec<PieceType>>,
+    #[serde(default = "default_win_conditions")]
+    pub(crate) win_conditions: Vec<WinCondition>,
+    #[serde(default)]
+    pub(crate) promotions: Vec<PromotionRule>,
+    /// How many full moves without a capture or pawn-like move draw the game, `None` to disable
+    #[serde(default = "default_move_rule")]
+    pub(crate) move_rule: Option<usize>,
+    /// Rows, counted from each side's own first row, where pieces can still make first move only moves when the fen is loaded
+    #[serde(default = "default_first_move_rows")]
+    pub(crate) first_move_rows: Vec<usize>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_win_conditions() -> Vec<WinCondition> {
+    vec![WinCondition::Checkmate]
+}
+
+fn default_move_rule() -> Option<usize> {
+    Some(50)
+}
+
+fn default_first_move_rows() -> Vec<usize> {
+    vec![1]
+}
+
+impl Variant {
+    pub(crate) fn from_json(json: &str) -> Result<Self, LinnyError> {
+        serde_json::from_str(json).map_err(|err| LinnyError::InvalidVariant(err.to_string()))
+    }
+
+    pub(crate) fn from_toml(toml: &str) -> Result<Self, LinnyError> {
+        toml::from_str(toml).map_err(|err| LinnyError::InvalidVariant(err.to_string()))
+    }
+
+    /// Reads a variant from json if it looks like a json object, otherwise from toml
+    pub(crate) fn parse(text: &str) -> Result<Self, LinnyError> {
+        if text.trim_start().starts_with('{') {
+            Self::from_json(text)
+        } else {
+            Self::from_toml(text)
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl Board {
+    /// Sets up a board for a variant in its starting position, checking every piece it defines
+    pub(crate) fn from_variant(variant: &Variant) -> Result<Board, LinnyError> {
+        let mut board = Board::new(variant.width, variant.height);
+        if !variant.default_pieces {
+            for id in default_pieces().keys() {
+                board.remove_piece(*id)?;
+            }
+        }
+        for info in variant.pieces.iter() {
+            if board.pieces.contains_key(&info.id) {
+                board.replace_piece(info.id, info.clone())?;
+            } else {
+                board.load_piece_as(info.id, info.clone());
+            }
+        }
+
+        if let Some(royal) = &variant.royal {
+            for id in royal.iter() {
+                board.piece_info(*id)?;
+            }
+            for (id, info) in board.pieces.iter_mut() {
+                info.royal = royal.contains(id);
+            }
+        }
+        for rule in variant.promotions.iter() {
+            board.piece_info(rule.piece)?;
+            let attributes = &mut board.pieces.get_mut(&rule.piece).unwrap().attributes;
+            attributes.retain(|attribute| !matches!(attribute, PieceAttribute::Promotion(_)));
+        }
+        // A piece can have several rules, like promoting on some rows only if it wants to
+        for rule in variant.promotions.iter() {
+            let attributes = &mut board.pieces.get_mut(&rule.piece).unwrap().attributes;
+            attributes.push(PieceAttribute::Promotion(rule.promotion.clone()));
+        }
+
+        let mut problems = vec![];
+        for (id, info) in board.pieces.iter() {
+            problems.extend(board.validate_piece(info, Some(*id)));
+        }
+        if !problems.is_empty() {
+            return Err(LinnyError::InvalidPiece(problems));
+        }
+
+        board.win_conditions = variant.win_conditions.clone();
+        board.move_rule = variant.move_rule;
+        board.first_move_rows = variant.first_move_rows.clone();
+        board.load_fen(&variant.fen)?;
+        Ok(board)
+    }
+}
+
+#[test]
+fn schemas() {
+    let schema = schemars::schema_for!(Variant);
+    println!("Variant:\n{}", serde_json::to_string(&schema).unwrap());
+}
+
+#[test]
+fn test_variant() {
+    use crate::board::GameStatus;
+    use crate::piece::{Color, DefaultPiece};
+
+    let toml = r#"
+name = "Mini chess"
+width = 5
+height = 5
+fen = "rnbqk/ppppp/5/PPPPP/RNBQK w - - 0 1"
+win_conditions = ["Checkmate", "BareKing"]
+
+[[promotions]]
+piece = 0
+rows = [0]
+pieces = [4]
+optional = false
+"#;
+    let variant = Variant::from_toml(toml).unwrap();
+    assert!(variant.default_pieces);
+    assert_eq!(variant.move_rule, Some(50));
+
+    let board = Board::from_variant(&variant).unwrap();
+    assert_eq!((board.width, board.height), (5, 5));
+    assert_eq!(board.win_conditions, variant.win_conditions);
+    assert_eq!(board.legal_moves(Color::WHITE).len(), 7);
+    assert_eq!(board.status(), GameStatus::Ongoing);
+
+    // The same variant survives a trip through json
+    let json = variant.to_json();
+    let board = Board::from_variant(&Variant::parse(&json).unwrap()).unwrap();
+    assert_eq!(board.to_fen(), "rnbqk/ppppp/5/PPPPP/RNBQK w - - 0 1");
+
+    // Pieces replace the default ones with the same id, and royalty can be moved to another piece
+    let mut variant = variant;
+    let mut ferz = board.pieces[&DefaultPiece::QUEEN].clone();
+    ferz.display = "Ferz".to_string();
+    ferz.icon = 'f';
+    ferz.value = 2;
+    variant.pieces.push(ferz);
+    variant.royal = Some(vec![DefaultPiece::KING, DefaultPiece::QUEEN]);
+    variant.fen = "rnbfk/ppppp/5/PPPPP/RNBFK w - - 0 1".to_string();
+    let board = Board::from_variant(&variant).unwrap();
+    assert_eq!(board.pieces[&DefaultPiece::QUEEN].display, "Ferz");
+    assert!(board.pieces[&DefaultPiece::QUEEN].royal);
+
+    variant.royal = Some(vec![99]);
+    assert!(matches!(
+        Board::from_variant(&variant),
+        Err(LinnyError::UnknownPieceType(99))
+    ));
+    variant.royal = None;
+    variant.pieces[0].icon = 'n';
+    assert!(matches!(
+        Board::from_variant(&variant),
+        Err(LinnyError::InvalidPiece(_))
+    ));
+    assert!(matches!(
+        Variant::from_json("{\"name\": \"No size\"}"),
+        Err(LinnyError::InvalidVariant(_))
+    ));
+
+    // Every rule for a piece is kept, like an optional promotion before the last row
+    let toml = r#"
+name = "Mini chess"
+width = 5
+height = 5
+fen = "4k/5/P4/5/K4 w - - 0 1"
+
+[[promotions]]
+piece = 0
+rows = [0]
+pieces = [4]
+optional = false
+
+[[promotions]]
+piece = 0
+rows = [1]
+pieces = [2]
+optional = true
+"#;
+    let mut board = Board::from_variant(&Variant::from_toml(toml).unwrap()).unwrap();
+    let promotions = board.pieces[&DefaultPiece::PAWN]
+        .attributes
+        .iter()
+        .filter(|attribute| matches!(attribute, PieceAttribute::Promotion(_)))
+        .count();
+    assert_eq!(promotions, 2);
+    let pawn = board.get(&crate::util::Loc(0, 2)).unwrap();
+    let promotions = pawn
+        .legal_moves(&board)
+        .iter()
+        .map(|move_data| move_data.promotion)
+        .collect::<Vec<_>>();
+    assert_eq!(promotions, vec![Some(DefaultPiece::KNIGHT), None]);
+    board
+        .move_piece(&board.parse_move("a3a4").unwrap())
+        .unwrap();
+    board
+        .move_piece(&board.parse_move("e5d5").unwrap())
+        .unwrap();
+    assert_eq!(
+        board.parse_move("a4a5q").unwrap().promotion,
+        Some(DefaultPiece::QUEEN)
+    );
+    assert!(board.parse_move("a4a5").is_err());
+}
@@ -0,0 +1,78 @@
+use rustc_hash::FxHashMap;
+
+use crate::hashmap;
+use crate::piece::{ColorType, PieceType};
+
+/// Random keys for every part of a position, xored together to hash it
+///
+/// Keys are derived from what they represent rather than generation order,
+/// so a piece type always gets the same keys no matter when it was loaded
+#[derive(Debug, Clone)]
+pub(crate) struct ZobristKeys {
+    /// - `pieces[color][piece_type][index]`
+    pub(crate) pieces: [FxHashMap<PieceType, Vec<u64>>; 2],
+    /// Keys for the squares of castling pieces and rooks that can still castle together, see `Board::castling_hash`
+    /// - `castling[color][index]`
+    pub(crate) castling: [Vec<u64>; 2],
+    /// Keys for the square of the piece that can be captured en passant
+    pub(crate) en_passant: Vec<u64>,
+    pub(crate) black_to_move: u64,
+    bitlength: usize,
+}
+
+/// SplitMix64, good enough to turn a seed into a random looking key
+fn key(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Gives every kind of key its own range of seeds
+fn keys(kind: u64, color: ColorType, piece_type: PieceType, bitlength: usize) -> Vec<u64> {
+    let seed = (kind << 56) ^ ((color as u64) << 48) ^ ((piece_type as u64) << 24);
+    (0..bitlength as u64)
+        .map(|index| key(seed ^ index))
+        .collect()
+}
+
+impl ZobristKeys {
+    const PIECE: u64 = 1;
+    const CASTLING: u64 = 2;
+    const EN_PASSANT: u64 = 3;
+    const TURN: u64 = 4;
+
+    pub(crate) fn new<'a>(
+        bitlength: usize,
+        piece_types: impl Iterator<Item = &'a PieceType>,
+    ) -> Self {
+        let mut zobrist = Self {
+            pieces: [hashmap! {}, hashmap! {}],
+            castling: [
+                keys(Self::CASTLING, 0, 0, bitlength),
+                keys(Self::CASTLING, 1, 0, bitlength),
+            ],
+            en_passant: keys(Self::EN_PASSANT, 0, 0, bitlength),
+            black_to_move: key(Self::TURN << 56),
+            bitlength,
+        };
+        for piece_type in piece_types {
+            zobrist.add_piece(*piece_type);
+        }
+        zobrist
+    }
+
+    /// Generates keys for a newly registered piece type
+    pub(crate) fn add_piece(&mut self, piece_type: PieceType) {
+        for (color, pieces) in self.pieces.iter_mut().enumerate() {
+            pieces.insert(
+                piece_type,
+                keys(Self::PIECE, color, piece_type, self.bitlength),
+            );
+        }
+    }
+
+    pub(crate) fn piece(&self, color: ColorType, piece_type: PieceType, index: usize) -> u64 {
+        self.pieces[color][&piece_type][index]
+    }
+}